@@ -27,7 +27,7 @@ fn main() {
 
     // Copy templates
     let bike = bicycle::Bicycle::default();
-    for rel in ["platforms", "apps"]
+    for rel in ["platforms", "apps", "shared"]
         .iter()
         .map(|prefix| Path::new("templates").join(prefix))
     {
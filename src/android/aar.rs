@@ -0,0 +1,84 @@
+use super::{
+    config::{Config, Kind},
+    device::{gradle_log_level, gradlew},
+    env::Env,
+    target::Target,
+};
+use crate::{
+    opts::{NoiseLevel, Profile},
+    util::cli::{Report, Reportable},
+};
+use std::{fs, io, path::PathBuf};
+
+#[derive(Debug)]
+pub enum Error {
+    NotLibrary,
+    LibSymlinkCleaningFailed(io::Error),
+    AssembleFailed(bossy::Error),
+    CopyFailed {
+        src: PathBuf,
+        dest: PathBuf,
+        cause: io::Error,
+    },
+}
+
+impl Reportable for Error {
+    fn report(&self) -> Report {
+        match self {
+            Self::NotLibrary => Report::action_request(
+                "Only library projects can be packaged as an AAR",
+                format!(
+                    "Set `{}.kind = \"library\"`, then run `cargo mobile init` and try again.",
+                    super::NAME
+                ),
+            ),
+            Self::LibSymlinkCleaningFailed(err) => {
+                Report::error("Failed to delete broken symlink", err)
+            }
+            Self::AssembleFailed(err) => Report::error("Failed to assemble AAR", err),
+            Self::CopyFailed { src, dest, cause } => Report::error(
+                format!("Failed to copy AAR from {:?} to {:?}", src, dest),
+                cause,
+            ),
+        }
+    }
+}
+
+fn aar_path(config: &Config, profile: Profile) -> PathBuf {
+    config.project_dir().join(format!(
+        "app/build/outputs/aar/app-{}.aar",
+        profile.as_str()
+    ))
+}
+
+// The Rust plugin builds every target before assembling, so this is just a
+// matter of kicking off Gradle and finding the output.
+pub fn build(
+    config: &Config,
+    env: &Env,
+    noise_level: NoiseLevel,
+    profile: Profile,
+    out: Option<PathBuf>,
+) -> Result<PathBuf, Error> {
+    use heck::CamelCase as _;
+    if config.kind() != Kind::Library {
+        return Err(Error::NotLibrary);
+    }
+    Target::clean_jnilibs(config).map_err(Error::LibSymlinkCleaningFailed)?;
+    gradlew(config, env)
+        .with_arg(format!("assemble{}", profile.as_str().to_camel_case()))
+        .with_arg(gradle_log_level(noise_level))
+        .run_and_wait()
+        .map_err(Error::AssembleFailed)?;
+    let src = aar_path(config, profile);
+    if let Some(dest) = out {
+        fs::copy(&src, &dest).map_err(|cause| Error::CopyFailed {
+            src,
+            dest: dest.clone(),
+            cause,
+        })?;
+        Ok(dest)
+    } else {
+        Ok(src)
+    }
+}
@@ -0,0 +1,278 @@
+use super::{config::Config, env::Env, target::Target};
+use crate::{
+    env::ExplicitEnv as _,
+    opts::Profile,
+    util::{
+        self,
+        cli::{Report, Reportable},
+    },
+};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+// Matches what the generated `build.gradle.kts` uses.
+const TARGET_SDK_VERSION: u32 = 28;
+
+#[derive(Debug)]
+pub enum Error {
+    BuildToolsMissing { sdk_root: PathBuf },
+    PlatformMissing { sdk_root: PathBuf },
+    ZipCheckFailed(bossy::Error),
+    ZipMissing,
+    NoHomeDir(util::NoHomeDir),
+    DirCreationFailed { path: PathBuf, cause: io::Error },
+    ResCompileFailed(bossy::Error),
+    LinkFailed(bossy::Error),
+    LibCopyFailed { src: PathBuf, cause: io::Error },
+    LibAddFailed(bossy::Error),
+    AlignFailed(bossy::Error),
+    KeystoreCreationFailed(bossy::Error),
+    SignFailed(bossy::Error),
+}
+
+impl Reportable for Error {
+    fn report(&self) -> Report {
+        match self {
+            Self::BuildToolsMissing { sdk_root } => Report::action_request(
+                format!("No build tools found in {:?}", sdk_root.join("build-tools")),
+                "Install them with `sdkmanager \"build-tools;30.0.3\"`, or through the SDK Manager in Android Studio.",
+            ),
+            Self::PlatformMissing { sdk_root } => Report::action_request(
+                format!("No SDK platforms found in {:?}", sdk_root.join("platforms")),
+                format!("Install one with `sdkmanager \"platforms;android-{}\"`, or through the SDK Manager in Android Studio.", TARGET_SDK_VERSION),
+            ),
+            Self::ZipCheckFailed(err) => {
+                Report::error("Failed to check for presence of `zip` command", err)
+            }
+            Self::ZipMissing => Report::action_request(
+                "`zip` command not found",
+                "The direct packager uses `zip` to add libs to the APK, so install it with your system's package manager, or set `android.packager` back to \"gradle\".",
+            ),
+            Self::NoHomeDir(err) => Report::error("Failed to locate debug keystore", err),
+            Self::DirCreationFailed { path, cause } => {
+                Report::error(format!("Failed to create directory at {:?}", path), cause)
+            }
+            Self::ResCompileFailed(err) => Report::error("Failed to compile resources", err),
+            Self::LinkFailed(err) => Report::error("Failed to link APK", err),
+            Self::LibCopyFailed { src, cause } => {
+                Report::error(format!("Failed to copy lib {:?} into APK", src), cause)
+            }
+            Self::LibAddFailed(err) => Report::error("Failed to add libs to APK", err),
+            Self::AlignFailed(err) => Report::error("Failed to align APK", err),
+            Self::KeystoreCreationFailed(err) => {
+                Report::error("Failed to create debug keystore", err)
+            }
+            Self::SignFailed(err) => Report::error("Failed to sign APK", err),
+        }
+    }
+}
+
+// Versions are compared numerically, so `30.0.3` beats `4.0.0`; anything that
+// doesn't parse (i.e. release candidates) is skipped.
+fn latest_versioned(dir: &Path, prefix: &str) -> Option<PathBuf> {
+    fs::read_dir(dir)
+        .ok()?
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            let version = name
+                .strip_prefix(prefix)?
+                .split('.')
+                .map(|part| part.parse::<u32>().ok())
+                .collect::<Option<Vec<_>>>()?;
+            Some((version, entry.path()))
+        })
+        .max_by(|(a, _), (b, _)| a.cmp(b))
+        .map(|(_, path)| path)
+}
+
+fn create_dir_all(path: &Path) -> Result<(), Error> {
+    fs::create_dir_all(path).map_err(|cause| Error::DirCreationFailed {
+        path: path.to_owned(),
+        cause,
+    })
+}
+
+// This is the same keystore Android Studio uses, so switching between
+// packagers doesn't force an uninstall.
+fn debug_keystore(env: &Env) -> Result<PathBuf, Error> {
+    let android_dir = util::home_dir().map_err(Error::NoHomeDir)?.join(".android");
+    let keystore = android_dir.join("debug.keystore");
+    if !keystore.is_file() {
+        println!("Creating debug keystore at {:?}...", keystore);
+        create_dir_all(&android_dir)?;
+        bossy::Command::pure("keytool")
+            .with_env_vars(env.explicit_env())
+            .with_args(&["-genkeypair", "-keystore"])
+            .with_arg(&keystore)
+            .with_args(&[
+                "-storepass",
+                "android",
+                "-alias",
+                "androiddebugkey",
+                "-keypass",
+                "android",
+                "-keyalg",
+                "RSA",
+                "-keysize",
+                "2048",
+                "-validity",
+                "10000",
+                "-dname",
+                "CN=Android Debug,O=Android,C=US",
+            ])
+            .run_and_wait()
+            .map_err(Error::KeystoreCreationFailed)?;
+    }
+    Ok(keystore)
+}
+
+// Gradle's native lib merging boils down to this: whatever `Target::build`
+// put in `jniLibs`, plus the debug source set's libs for debug builds.
+fn copy_libs(config: &Config, target: &Target, profile: Profile, dest: &Path) -> Result<(), Error> {
+    let app_dir = config.project_dir().join("app/src");
+    let mut src_dirs = vec![app_dir.join("main/jniLibs").join(target.abi)];
+    if profile.debug() {
+        src_dirs.push(app_dir.join("debug/jniLibs").join(target.abi));
+    }
+    let dest = dest.join("lib").join(target.abi);
+    create_dir_all(&dest)?;
+    for src_dir in src_dirs {
+        let entries = match fs::read_dir(&src_dir) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+        for src in entries.filter_map(Result::ok).map(|entry| entry.path()) {
+            if src.extension().map_or(false, |ext| ext == "so") && src.is_file() {
+                let file_name = src
+                    .file_name()
+                    .expect("developer error: lib path from `read_dir` had no file name");
+                fs::copy(&src, dest.join(file_name))
+                    .map_err(|cause| Error::LibCopyFailed { src, cause })?;
+            }
+        }
+    }
+    Ok(())
+}
+
+// Builds the APK straight from the SDK build tools, skipping Gradle entirely.
+// The output lands where Gradle would put it, so everything downstream (i.e.
+// installing) works the same regardless of packager.
+pub fn build(config: &Config, env: &Env, target: &Target, profile: Profile) -> Result<(), Error> {
+    let build_tools =
+        latest_versioned(&env.sdk_root().join("build-tools"), "").ok_or_else(|| {
+            Error::BuildToolsMissing {
+                sdk_root: env.sdk_root().to_owned(),
+            }
+        })?;
+    let platform =
+        latest_versioned(&env.sdk_root().join("platforms"), "android-").ok_or_else(|| {
+            Error::PlatformMissing {
+                sdk_root: env.sdk_root().to_owned(),
+            }
+        })?;
+    if !util::command_present("zip").map_err(Error::ZipCheckFailed)? {
+        return Err(Error::ZipMissing);
+    }
+    log::info!("packaging with {:?} and {:?}", build_tools, platform);
+    let tool =
+        |name: &str| bossy::Command::pure(build_tools.join(name)).with_env_vars(env.explicit_env());
+
+    let app_dir = config.project_dir().join("app");
+    let work_dir = app_dir
+        .join("build/direct")
+        .join(target.arch)
+        .join(profile.as_str());
+    if work_dir.is_dir() {
+        fs::remove_dir_all(&work_dir).map_err(|cause| Error::DirCreationFailed {
+            path: work_dir.clone(),
+            cause,
+        })?;
+    }
+    create_dir_all(&work_dir)?;
+
+    let compiled_res = work_dir.join("res.zip");
+    tool("aapt2")
+        .with_args(&["compile", "--dir"])
+        .with_arg(app_dir.join("src/main/res"))
+        .with_arg("-o")
+        .with_arg(&compiled_res)
+        .run_and_wait()
+        .map_err(Error::ResCompileFailed)?;
+
+    let unaligned = work_dir.join("unaligned.apk");
+    let mut link = tool("aapt2");
+    link.add_args(&["link", "-o"])
+        .add_arg(&unaligned)
+        .add_arg("-I")
+        .add_arg(platform.join("android.jar"))
+        .add_arg("--manifest")
+        .add_arg(app_dir.join("src/main/AndroidManifest.xml"))
+        .add_args(&["--min-sdk-version", &config.min_sdk_version().to_string()])
+        .add_args(&["--target-sdk-version", &TARGET_SDK_VERSION.to_string()])
+        .add_args(&["--version-code", "1", "--version-name", "1.0"]);
+    let assets = app_dir.join("src/main/assets");
+    if assets.is_dir() {
+        link.add_arg("-A").add_arg(assets);
+    }
+    if profile.debug() {
+        link.add_arg("--debug-mode");
+    }
+    link.add_arg(&compiled_res)
+        .run_and_wait()
+        .map_err(Error::LinkFailed)?;
+
+    // `zip` stores paths relative to the working directory, and the libs have
+    // to end up under `lib/<abi>/`.
+    let staging = work_dir.join("staging");
+    copy_libs(config, target, profile, &staging)?;
+    bossy::Command::pure("sh")
+        .with_env_vars(env.explicit_env())
+        .with_arg("-c")
+        .with_arg(format!(
+            "cd {} && zip -q -r {} lib",
+            util::shell_quote(&staging),
+            util::shell_quote(&unaligned),
+        ))
+        .run_and_wait()
+        .map_err(Error::LibAddFailed)?;
+
+    let apk_path = target.apk_path(config, profile);
+    let aligned = if profile.debug() {
+        work_dir.join("aligned.apk")
+    } else {
+        apk_path.clone()
+    };
+    if let Some(parent) = apk_path.parent() {
+        create_dir_all(parent)?;
+    }
+    tool("zipalign")
+        .with_args(&["-f", "-p", "4"])
+        .with_arg(&unaligned)
+        .with_arg(&aligned)
+        .run_and_wait()
+        .map_err(Error::AlignFailed)?;
+
+    // Like Gradle, release APKs are left unsigned.
+    if profile.debug() {
+        tool("apksigner")
+            .with_args(&["sign", "--ks"])
+            .with_arg(debug_keystore(env)?)
+            .with_args(&[
+                "--ks-pass",
+                "pass:android",
+                "--key-pass",
+                "pass:android",
+                "--ks-key-alias",
+                "androiddebugkey",
+                "--out",
+            ])
+            .with_arg(&apk_path)
+            .with_arg(&aligned)
+            .run_and_wait()
+            .map_err(Error::SignFailed)?;
+    }
+    Ok(())
+}
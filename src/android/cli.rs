@@ -1,10 +1,16 @@
 use crate::{
     android::{
-        adb,
-        config::{Config, Metadata},
-        device::{Device, RunError, StacktraceError},
+        aar, adb,
+        config::{Config, Kind, Metadata},
+        debug,
+        device::{AssetSyncError, Device, RunError, RunOptions, ToolError},
         env::{Env, Error as EnvError},
-        target::{BuildError, CompileLibError, Target},
+        logcat,
+        ndk::MissingToolError,
+        profile,
+        size::{self, Breakdown},
+        stacktrace::{self, StacktraceError},
+        target::{BuildError, CargoPassthroughError, CompileLibError, Target},
         NAME,
     },
     config::{
@@ -14,10 +20,12 @@ use crate::{
     define_device_prompt,
     device::PromptError,
     opts, os,
-    target::{call_for_targets_with_fallback, TargetInvalid, TargetTrait as _},
+    target::{call_for_targets, call_for_targets_with_defaults, TargetInvalid, TargetTrait as _},
     util::{
+        self,
         cli::{self, Exec, GlobalFlags, Report, Reportable, TextWrapper, VERSION_INFO},
         prompt,
+        watch::Watcher,
     },
 };
 use std::path::PathBuf;
@@ -49,25 +57,213 @@ pub enum Command {
     Open,
     #[structopt(name = "check", about = "Checks if code compiles for target(s)")]
     Check {
-        #[structopt(name = "targets", default_value = Target::DEFAULT_KEY, possible_values = Target::name_list())]
+        #[structopt(name = "targets", possible_values = Target::name_list())]
         targets: Vec<String>,
+        #[structopt(
+            long = "device-target",
+            help = "Use the target of a connected device when no targets are given"
+        )]
+        device_target: bool,
+        #[structopt(long = "watch", help = "Check again whenever sources change")]
+        watch: bool,
     },
     #[structopt(name = "build", about = "Builds dynamic libraries for target(s)")]
     Build {
-        #[structopt(name = "targets", default_value = Target::DEFAULT_KEY, possible_values = Target::name_list())]
+        #[structopt(name = "targets", possible_values = Target::name_list())]
         targets: Vec<String>,
+        #[structopt(
+            long = "device-target",
+            help = "Use the target of a connected device when no targets are given"
+        )]
+        device_target: bool,
         #[structopt(flatten)]
         profile: cli::Profile,
+        #[structopt(
+            long = "strip-symbols",
+            help = "Strip release libs and archive their debug symbols in `symbols/`, even if `android.strip-release-libs` isn't set",
+            parse(from_flag = opts::StripSymbols::from_bool)
+        )]
+        strip_symbols: opts::StripSymbols,
+    },
+    #[structopt(
+        name = "cargo",
+        about = "Runs a cargo subcommand with the Android environment for target(s)"
+    )]
+    Cargo {
+        #[structopt(name = "targets", possible_values = Target::name_list())]
+        targets: Vec<String>,
+        #[structopt(
+            long = "device-target",
+            help = "Use the target of a connected device when no targets are given"
+        )]
+        device_target: bool,
+        #[structopt(flatten)]
+        profile: cli::Profile,
+        #[structopt(
+            name = "args",
+            help = "Cargo subcommand and its arguments, i.e. `-- clippy --all-targets`",
+            last = true,
+            required = true
+        )]
+        args: Vec<String>,
+    },
+    #[structopt(
+        name = "env",
+        about = "Prints the Android environment for a target as shell exports"
+    )]
+    Env {
+        #[structopt(name = "target", default_value = Target::DEFAULT_KEY, possible_values = Target::name_list())]
+        target: String,
+    },
+    #[structopt(
+        name = "aar",
+        about = "Packages all targets into an AAR for use in other apps"
+    )]
+    Aar {
+        #[structopt(flatten)]
+        profile: cli::Profile,
+        #[structopt(
+            long = "out",
+            help = "Copy the AAR to this path once it's built",
+            parse(from_os_str)
+        )]
+        out: Option<PathBuf>,
     },
     #[structopt(name = "run", about = "Deploys APK to connected device")]
     Run {
         #[structopt(flatten)]
         profile: cli::Profile,
+        #[structopt(flatten)]
+        options: RunOptions,
+    },
+    #[structopt(name = "debug", about = "Deploys APK and attaches LLDB to it")]
+    Debug {
+        #[structopt(flatten)]
+        profile: cli::Profile,
+        #[structopt(flatten)]
+        options: debug::Options,
+    },
+    #[structopt(
+        name = "sync-assets",
+        about = "Pushes changed assets to a device without reinstalling"
+    )]
+    SyncAssets {
+        #[structopt(long = "restart", help = "Restart the app once assets are pushed")]
+        restart: bool,
+    },
+    #[structopt(name = "logs", about = "Shows filtered logs from the app on a device")]
+    Logs {
+        #[structopt(flatten)]
+        options: logcat::Options,
     },
     #[structopt(name = "st", about = "Displays a detailed stacktrace for a device")]
-    Stacktrace,
+    Stacktrace {
+        #[structopt(
+            long = "from-file",
+            help = "Read a saved log or tombstone instead of using a connected device",
+            parse(from_os_str)
+        )]
+        from_file: Option<PathBuf>,
+        #[structopt(
+            long = "bugreport",
+            help = "Pull the latest tombstone via `adb bugreport` if it isn't directly readable"
+        )]
+        bugreport: bool,
+        #[structopt(flatten)]
+        profile: cli::Profile,
+        #[structopt(flatten)]
+        output_format: cli::OutputFormat,
+    },
+    #[structopt(
+        name = "profile",
+        about = "Records a CPU profile of the running app with simpleperf"
+    )]
+    Profile {
+        #[structopt(flatten)]
+        profile: cli::Profile,
+        #[structopt(flatten)]
+        options: profile::Options,
+    },
+    #[structopt(name = "size", about = "Shows what's taking up space in a built APK")]
+    Size {
+        #[structopt(name = "target", default_value = Target::DEFAULT_KEY, possible_values = Target::name_list())]
+        target: String,
+        #[structopt(flatten)]
+        profile: cli::Profile,
+        #[structopt(
+            long = "apk",
+            help = "APK to inspect, instead of the one built for the target",
+            parse(from_os_str)
+        )]
+        apk: Option<PathBuf>,
+        #[structopt(
+            long = "compare",
+            help = "Show size changes relative to this APK",
+            parse(from_os_str)
+        )]
+        compare: Option<PathBuf>,
+        #[structopt(
+            long = "top",
+            help = "How many crates and functions to list per lib",
+            default_value = "20"
+        )]
+        top: usize,
+        #[structopt(flatten)]
+        output_format: cli::OutputFormat,
+    },
     #[structopt(name = "list", about = "Lists connected devices")]
     List,
+    #[structopt(
+        name = "device",
+        about = "Utilities for working with a connected device"
+    )]
+    Device {
+        #[structopt(subcommand)]
+        command: DeviceCommand,
+    },
+}
+
+#[derive(Clone, Debug, StructOpt)]
+pub enum DeviceCommand {
+    #[structopt(name = "screenshot", about = "Saves a screenshot as a PNG")]
+    Screenshot {
+        #[structopt(name = "path", parse(from_os_str))]
+        path: Option<PathBuf>,
+    },
+    #[structopt(name = "record", about = "Records the screen as an MP4")]
+    Record {
+        #[structopt(name = "path", parse(from_os_str))]
+        path: Option<PathBuf>,
+        #[structopt(
+            long = "duration",
+            help = "How many seconds to record for (at most 180)",
+            default_value = "10"
+        )]
+        duration: u32,
+    },
+    #[structopt(name = "shell", about = "Opens a shell, or runs a command in one")]
+    Shell {
+        #[structopt(name = "args")]
+        args: Vec<String>,
+    },
+    #[structopt(name = "push", about = "Copies a file into the app's data dir")]
+    Push {
+        #[structopt(name = "src", parse(from_os_str))]
+        src: PathBuf,
+        #[structopt(name = "dest", help = "Path relative to the app's data dir")]
+        dest: String,
+    },
+    #[structopt(name = "pull", about = "Copies a file out of the app's data dir")]
+    Pull {
+        #[structopt(name = "src", help = "Path relative to the app's data dir")]
+        src: String,
+        #[structopt(name = "dest", parse(from_os_str))]
+        dest: Option<PathBuf>,
+    },
+    #[structopt(name = "uninstall", about = "Uninstalls the app")]
+    Uninstall,
+    #[structopt(name = "clear-data", about = "Clears the app's data")]
+    ClearData,
 }
 
 #[derive(Debug)]
@@ -82,9 +278,19 @@ pub enum Error {
     OpenFailed(bossy::Error),
     CheckFailed(CompileLibError),
     BuildFailed(BuildError),
+    CargoFailed(CargoPassthroughError),
+    EnvFailed(MissingToolError),
+    AarFailed(aar::Error),
+    LibraryNotRunnable,
     RunFailed(RunError),
+    DebugFailed(debug::Error),
+    SyncAssetsFailed(AssetSyncError),
+    LogsFailed(logcat::Error),
     StacktraceFailed(StacktraceError),
+    ProfileFailed(profile::Error),
+    SizeFailed(size::Error),
     ListFailed(adb::device_list::Error),
+    DeviceCommandFailed(ToolError),
 }
 
 impl Reportable for Error {
@@ -106,9 +312,22 @@ impl Reportable for Error {
             Self::OpenFailed(err) => Report::error("Failed to open project in Android Studio", err),
             Self::CheckFailed(err) => err.report(),
             Self::BuildFailed(err) => err.report(),
+            Self::CargoFailed(err) => err.report(),
+            Self::EnvFailed(err) => Report::error("Failed to locate required build tool", err),
+            Self::AarFailed(err) => err.report(),
+            Self::LibraryNotRunnable => Report::action_request(
+                "Library projects can't be run on their own",
+                "Use `cargo android aar` to build an AAR, then add it to the app that uses it.",
+            ),
             Self::RunFailed(err) => err.report(),
+            Self::DebugFailed(err) => err.report(),
+            Self::SyncAssetsFailed(err) => err.report(),
+            Self::LogsFailed(err) => err.report(),
             Self::StacktraceFailed(err) => err.report(),
+            Self::ProfileFailed(err) => err.report(),
+            Self::SizeFailed(err) => err.report(),
             Self::ListFailed(err) => err.report(),
+            Self::DeviceCommandFailed(err) => err.report(),
         }
     }
 }
@@ -170,11 +389,17 @@ impl Exec for Input {
                 ensure_init(config)?;
                 open_in_android_studio(config)
             }),
-            Command::Check { targets } => {
-                with_config(non_interactive, wrapper, |config, metadata| {
-                    let force_color = opts::ForceColor::Yes;
-                    call_for_targets_with_fallback(
+            Command::Check {
+                targets,
+                device_target,
+                watch,
+            } => with_config(non_interactive, wrapper, |config, metadata| {
+                let force_color = opts::ForceColor::Yes;
+                let check = || {
+                    call_for_targets_with_defaults(
                         targets.iter(),
+                        config.default_targets(),
+                        device_target,
                         &detect_target_ok,
                         &env,
                         |target: &Target| {
@@ -184,47 +409,244 @@ impl Exec for Input {
                         },
                     )
                     .map_err(Error::TargetInvalid)?
-                })
-            }
+                };
+                if !watch {
+                    return check();
+                }
+                let mut watcher = Watcher::new(config.app().watch_paths());
+                loop {
+                    if let Err(err) = check() {
+                        err.report().print(wrapper);
+                    }
+                    println!("Watching for changes; press Ctrl-C to stop...");
+                    watcher.wait();
+                }
+            }),
             Command::Build {
                 targets,
+                device_target,
                 profile: cli::Profile { profile },
+                strip_symbols,
             } => with_config(non_interactive, wrapper, |config, metadata| {
                 ensure_init(config)?;
                 let force_color = opts::ForceColor::Yes;
-                call_for_targets_with_fallback(
+                call_for_targets_with_defaults(
                     targets.iter(),
+                    config.default_targets(),
+                    device_target,
                     &detect_target_ok,
                     &env,
                     |target: &Target| {
                         target
-                            .build(config, metadata, &env, noise_level, force_color, profile)
+                            .build(
+                                config,
+                                metadata,
+                                &env,
+                                noise_level,
+                                force_color,
+                                profile,
+                                strip_symbols,
+                            )
                             .map_err(Error::BuildFailed)
                     },
                 )
                 .map_err(Error::TargetInvalid)?
             }),
-            Command::Run {
+            Command::Cargo {
+                targets,
+                device_target,
+                profile: cli::Profile { profile },
+                args,
+            } => with_config(non_interactive, wrapper, |config, metadata| {
+                let (subcommand, args) = args
+                    .split_first()
+                    .expect("developer error: `args` is required");
+                let force_color = opts::ForceColor::Yes;
+                call_for_targets_with_defaults(
+                    targets.iter(),
+                    config.default_targets(),
+                    device_target,
+                    &detect_target_ok,
+                    &env,
+                    |target: &Target| {
+                        target
+                            .cargo_passthrough(
+                                config,
+                                metadata,
+                                &env,
+                                noise_level,
+                                force_color,
+                                profile,
+                                subcommand,
+                                args,
+                            )
+                            .map_err(Error::CargoFailed)
+                    },
+                )
+                .map_err(Error::TargetInvalid)?
+            }),
+            Command::Env { target } => with_config(non_interactive, wrapper, |config, _| {
+                call_for_targets(std::iter::once(&target), |target: &Target| {
+                    println!(
+                        "export ANDROID_SDK_ROOT={}",
+                        util::shell_quote(env.sdk_root())
+                    );
+                    println!("export NDK_HOME={}", util::shell_quote(env.ndk.home()));
+                    for (key, value) in target.cargo_env(config, &env).map_err(Error::EnvFailed)? {
+                        println!("export {}={}", key, util::shell_quote(&value));
+                    }
+                    Ok(())
+                })
+                .map_err(Error::TargetInvalid)?
+            }),
+            Command::Aar {
                 profile: cli::Profile { profile },
+                out,
             } => with_config(non_interactive, wrapper, |config, _| {
                 ensure_init(config)?;
-                device_prompt(&env)
-                    .map_err(Error::DevicePromptFailed)?
-                    .run(config, &env, noise_level, profile)
-                    .map_err(Error::RunFailed)
+                let path = aar::build(config, &env, noise_level, profile, out)
+                    .map_err(Error::AarFailed)?;
+                println!("AAR written to {:?}", path);
+                Ok(())
             }),
-            Command::Stacktrace => with_config(non_interactive, wrapper, |config, _| {
+            Command::Run {
+                profile: cli::Profile { profile },
+                options,
+            } => with_config(non_interactive, wrapper, |config, metadata| {
                 ensure_init(config)?;
+                if config.kind() == Kind::Library {
+                    return Err(Error::LibraryNotRunnable);
+                }
+                let device = device_prompt(&env).map_err(Error::DevicePromptFailed)?;
+                device
+                    .run(
+                        config,
+                        metadata,
+                        &env,
+                        noise_level,
+                        non_interactive,
+                        profile,
+                        &options,
+                    )
+                    .map_err(Error::RunFailed)?;
+                if options.watch {
+                    device.watch(
+                        config,
+                        metadata,
+                        &env,
+                        noise_level,
+                        profile,
+                        &options,
+                        wrapper,
+                    );
+                }
+                Ok(())
+            }),
+            Command::Debug {
+                profile: cli::Profile { profile },
+                options,
+            } => with_config(non_interactive, wrapper, |config, metadata| {
+                ensure_init(config)?;
+                if config.kind() == Kind::Library {
+                    return Err(Error::LibraryNotRunnable);
+                }
+                let device = device_prompt(&env).map_err(Error::DevicePromptFailed)?;
+                debug::attach(
+                    &device,
+                    config,
+                    metadata,
+                    &env,
+                    noise_level,
+                    non_interactive,
+                    profile,
+                    &options,
+                )
+                .map_err(Error::DebugFailed)
+            }),
+            Command::SyncAssets { restart } => {
+                with_config(non_interactive, wrapper, |config, _| {
+                    device_prompt(&env)
+                        .map_err(Error::DevicePromptFailed)?
+                        .sync_assets(config, &env, restart)
+                        .map_err(Error::SyncAssetsFailed)
+                })
+            }
+            Command::Logs { options } => with_config(non_interactive, wrapper, |config, _| {
                 device_prompt(&env)
                     .map_err(Error::DevicePromptFailed)?
-                    .stacktrace(config, &env)
-                    .map_err(Error::StacktraceFailed)
+                    .logs(config, &env, noise_level, &options)
+                    .map_err(Error::LogsFailed)
+            }),
+            Command::Stacktrace {
+                from_file,
+                bugreport,
+                profile: cli::Profile { profile },
+                output_format: cli::OutputFormat { output_format },
+            } => with_config(non_interactive, wrapper, |config, _| {
+                if let Some(path) = from_file {
+                    stacktrace::from_file(config, &env, profile, &path, output_format)
+                        .map_err(Error::StacktraceFailed)
+                } else {
+                    ensure_init(config)?;
+                    device_prompt(&env)
+                        .map_err(Error::DevicePromptFailed)?
+                        .stacktrace(config, &env, profile, bugreport, output_format)
+                        .map_err(Error::StacktraceFailed)
+                }
+            }),
+            Command::Profile {
+                profile: cli::Profile { profile },
+                options,
+            } => with_config(non_interactive, wrapper, |config, _| {
+                let device = device_prompt(&env).map_err(Error::DevicePromptFailed)?;
+                profile::run(&device, config, &env, profile, &options)
+                    .map(|out| println!("Wrote profile to {:?}", out))
+                    .map_err(Error::ProfileFailed)
+            }),
+            Command::Size {
+                target,
+                profile: cli::Profile { profile },
+                apk,
+                compare,
+                top,
+                output_format: cli::OutputFormat { output_format },
+            } => with_config(non_interactive, wrapper, |config, _| {
+                call_for_targets(std::iter::once(&target), |target: &Target| {
+                    let apk = apk.unwrap_or_else(|| target.apk_path(config, profile));
+                    let current = Breakdown::new(config, &env, profile, &apk, top)
+                        .map_err(Error::SizeFailed)?;
+                    let base = compare
+                        .map(|compare| Breakdown::for_comparison(&env, &compare, top))
+                        .transpose()
+                        .map_err(Error::SizeFailed)?;
+                    size::report(&current, base.as_ref(), output_format).map_err(Error::SizeFailed)
+                })
+                .map_err(Error::TargetInvalid)?
             }),
             Command::List => adb::device_list(&env)
                 .map_err(Error::ListFailed)
                 .map(|device_list| {
                     prompt::list_display_only(device_list.iter(), device_list.len());
                 }),
+            Command::Device { command } => with_config(non_interactive, wrapper, |config, _| {
+                let device = device_prompt(&env).map_err(Error::DevicePromptFailed)?;
+                match command {
+                    DeviceCommand::Screenshot { path } => device
+                        .screenshot(config, &env, path)
+                        .map(|path| println!("Saved screenshot to {:?}", path)),
+                    DeviceCommand::Record { path, duration } => device
+                        .record(config, &env, path, duration)
+                        .map(|path| println!("Saved recording to {:?}", path)),
+                    DeviceCommand::Shell { args } => device.shell(&env, &args),
+                    DeviceCommand::Push { src, dest } => device.push(config, &env, &src, &dest),
+                    DeviceCommand::Pull { src, dest } => device
+                        .pull(config, &env, &src, dest)
+                        .map(|path| println!("Saved {:?} to {:?}", src, path)),
+                    DeviceCommand::Uninstall => device.uninstall_app(config, &env),
+                    DeviceCommand::ClearData => device.clear_data(config, &env),
+                }
+                .map_err(Error::DeviceCommandFailed)
+            }),
         }
     }
 }
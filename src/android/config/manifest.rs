@@ -0,0 +1,304 @@
+use heck::MixedCase as _;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display},
+};
+
+static SCREEN_ORIENTATIONS: &[&str] = &[
+    "unspecified",
+    "behind",
+    "landscape",
+    "portrait",
+    "reverseLandscape",
+    "reversePortrait",
+    "sensorLandscape",
+    "sensorPortrait",
+    "userLandscape",
+    "userPortrait",
+    "sensor",
+    "fullSensor",
+    "nosensor",
+    "user",
+    "fullUser",
+    "locked",
+];
+
+static CONFIG_CHANGES: &[&str] = &[
+    "colorMode",
+    "density",
+    "fontScale",
+    "keyboard",
+    "keyboardHidden",
+    "layoutDirection",
+    "locale",
+    "mcc",
+    "mnc",
+    "navigation",
+    "orientation",
+    "screenLayout",
+    "screenSize",
+    "smallestScreenSize",
+    "touchscreen",
+    "uiMode",
+];
+
+static DEFAULT_CONFIG_CHANGES: &[&str] = &["orientation", "keyboardHidden"];
+
+// i.e. `com.example.Thing`
+pub(super) fn is_qualified_name(name: &str) -> bool {
+    name.contains('.')
+        && name.split('.').all(|segment| {
+            let mut chars = segment.chars();
+            chars
+                .next()
+                .map_or(false, |c| c.is_ascii_alphabetic() || c == '_')
+                && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+        })
+}
+
+// Both `sensor-landscape` and `sensorLandscape` are accepted, since the former
+// is more at home in a TOML file.
+fn normalize(value: &str, valid: &[&str]) -> Option<String> {
+    let value = if value.contains('-') {
+        value.to_mixed_case()
+    } else {
+        value.to_owned()
+    };
+    if valid.contains(&value.as_str()) {
+        Some(value)
+    } else {
+        None
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    PermissionInvalid { permission: String },
+    FeatureInvalid { feature: String },
+    ScreenOrientationInvalid { screen_orientation: String },
+    ConfigChangeInvalid { config_change: String },
+    DeepLinkSchemeInvalid { scheme: String },
+    DeepLinkAutoVerifyInvalid { scheme: String },
+    MetaDataNameEmpty,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::PermissionInvalid { permission } => write!(
+                f,
+                "permission {:?} is neither a standard permission like \"INTERNET\" nor a fully-qualified name",
+                permission
+            ),
+            Self::FeatureInvalid { feature } => write!(
+                f,
+                "feature {:?} is neither \"vulkan\", \"vulkan-1.1\", \"gles2\", \"gles3\", \"gles3.1\", \"gles3.2\", nor a fully-qualified name",
+                feature
+            ),
+            Self::ScreenOrientationInvalid { screen_orientation } => write!(
+                f,
+                "screen orientation {:?} isn't one of {:?}",
+                screen_orientation, SCREEN_ORIENTATIONS
+            ),
+            Self::ConfigChangeInvalid { config_change } => write!(
+                f,
+                "config change {:?} isn't one of {:?}",
+                config_change, CONFIG_CHANGES
+            ),
+            Self::DeepLinkSchemeInvalid { scheme } => {
+                write!(f, "deep link scheme {:?} isn't a valid URI scheme", scheme)
+            }
+            Self::DeepLinkAutoVerifyInvalid { scheme } => write!(
+                f,
+                "deep link with scheme {:?} can't be auto-verified; only `http` and `https` links with a host can be",
+                scheme
+            ),
+            Self::MetaDataNameEmpty => write!(f, "meta-data names can't be empty"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct DeepLink {
+    scheme: String,
+    host: Option<String>,
+    path_prefix: Option<String>,
+    #[serde(default)]
+    auto_verify: bool,
+}
+
+impl DeepLink {
+    fn validate(&self) -> Result<(), Error> {
+        let mut chars = self.scheme.chars();
+        let scheme_valid = chars.next().map_or(false, |c| c.is_ascii_lowercase())
+            && chars.all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || "+-.".contains(c));
+        if !scheme_valid {
+            Err(Error::DeepLinkSchemeInvalid {
+                scheme: self.scheme.clone(),
+            })
+        } else if self.auto_verify
+            && (!matches!(self.scheme.as_str(), "http" | "https") || self.host.is_none())
+        {
+            Err(Error::DeepLinkAutoVerifyInvalid {
+                scheme: self.scheme.clone(),
+            })
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Feature {
+    name: Option<String>,
+    version: Option<String>,
+    gl_es_version: Option<String>,
+}
+
+impl Feature {
+    fn from_raw(raw: String) -> Result<Self, Error> {
+        let named = |name: &str, version: Option<&str>| Self {
+            name: Some(name.to_owned()),
+            version: version.map(ToOwned::to_owned),
+            gl_es_version: None,
+        };
+        let gl_es = |version: &str| Self {
+            name: None,
+            version: None,
+            gl_es_version: Some(version.to_owned()),
+        };
+        match raw.as_str() {
+            "vulkan" => Ok(named("android.hardware.vulkan.version", Some("0x400003"))),
+            "vulkan-1.1" => Ok(named("android.hardware.vulkan.version", Some("0x401000"))),
+            "gles2" => Ok(gl_es("0x00020000")),
+            "gles3" => Ok(gl_es("0x00030000")),
+            "gles3.1" => Ok(gl_es("0x00030001")),
+            "gles3.2" => Ok(gl_es("0x00030002")),
+            _ if is_qualified_name(&raw) => Ok(named(&raw, None)),
+            _ => Err(Error::FeatureInvalid { feature: raw }),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct MetaData {
+    name: String,
+    value: String,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Raw {
+    permissions: Option<Vec<String>>,
+    features: Option<Vec<String>>,
+    screen_orientation: Option<String>,
+    config_changes: Option<Vec<String>>,
+    deep_links: Option<Vec<DeepLink>>,
+    meta_data: Option<BTreeMap<String, String>>,
+    tv: Option<bool>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Manifest {
+    permissions: Vec<String>,
+    features: Vec<Feature>,
+    screen_orientation: Option<String>,
+    config_changes: String,
+    deep_links: Vec<DeepLink>,
+    meta_data: Vec<MetaData>,
+    tv: bool,
+}
+
+impl Manifest {
+    pub fn from_raw(raw: Option<Raw>) -> Result<Self, Error> {
+        let raw = raw.unwrap_or_default();
+
+        let permissions = raw
+            .permissions
+            .unwrap_or_default()
+            .into_iter()
+            .map(|permission| {
+                let short = !permission.is_empty()
+                    && permission
+                        .chars()
+                        .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_');
+                if short {
+                    Ok(format!("android.permission.{}", permission))
+                } else if is_qualified_name(&permission) {
+                    Ok(permission)
+                } else {
+                    Err(Error::PermissionInvalid { permission })
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let features = raw
+            .features
+            .unwrap_or_default()
+            .into_iter()
+            .map(Feature::from_raw)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let screen_orientation = raw
+            .screen_orientation
+            .map(|screen_orientation| {
+                normalize(&screen_orientation, SCREEN_ORIENTATIONS)
+                    .ok_or(Error::ScreenOrientationInvalid { screen_orientation })
+            })
+            .transpose()?;
+
+        let config_changes = raw
+            .config_changes
+            .unwrap_or_else(|| {
+                log::info!(
+                    "`{}.manifest.config-changes` not set; defaulting to {:?}",
+                    super::super::NAME,
+                    DEFAULT_CONFIG_CHANGES
+                );
+                DEFAULT_CONFIG_CHANGES
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect()
+            })
+            .into_iter()
+            .map(|config_change| {
+                normalize(&config_change, CONFIG_CHANGES)
+                    .ok_or(Error::ConfigChangeInvalid { config_change })
+            })
+            .collect::<Result<Vec<_>, _>>()?
+            .join("|");
+
+        let deep_links = raw.deep_links.unwrap_or_default();
+        for deep_link in &deep_links {
+            deep_link.validate()?;
+        }
+
+        let meta_data = raw
+            .meta_data
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(name, value)| {
+                if name.is_empty() {
+                    Err(Error::MetaDataNameEmpty)
+                } else {
+                    Ok(MetaData { name, value })
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self {
+            permissions,
+            features,
+            screen_orientation,
+            config_changes,
+            deep_links,
+            meta_data,
+            tv: raw.tv.unwrap_or_default(),
+        })
+    }
+}
@@ -0,0 +1,658 @@
+mod manifest;
+
+use self::manifest::Manifest;
+
+use super::target::Target;
+use crate::{
+    config::app::App,
+    target::TargetTrait as _,
+    util::{self, cli::Report},
+};
+use serde::{Deserialize, Serialize, Serializer};
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display},
+    path::{Path, PathBuf},
+};
+
+const DEFAULT_MIN_SDK_VERSION: u32 = 24;
+const DEFAULT_VULKAN_VALIDATION: bool = true;
+static DEFAULT_LINK_LIBS: &[&str] = &["android", "log", "OpenSLES"];
+const DEFAULT_BUNDLE_SHARED_LIBS: bool = false;
+const DEFAULT_STRIP_RELEASE_LIBS: bool = false;
+// These match the scheme env vars in the Xcode project.
+static DEFAULT_LAUNCH_ENV: &[(&str, &str)] = &[("RUST_BACKTRACE", "full"), ("RUST_LOG", "info")];
+static DEFAULT_PROJECT_DIR: &str = "gen/android";
+static DEFAULT_ACTIVITY: &str = "native-activity";
+const DEFAULT_PACKAGER: Packager = Packager::Gradle;
+const DEFAULT_KIND: Kind = Kind::Application;
+
+const fn default_true() -> bool {
+    true
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Metadata {
+    #[serde(default = "default_true")]
+    supported: bool,
+    #[serde(default)]
+    features: Option<Vec<String>>,
+}
+
+impl Default for Metadata {
+    fn default() -> Self {
+        Self {
+            supported: true,
+            features: None,
+        }
+    }
+}
+
+impl Metadata {
+    pub const fn supported(&self) -> bool {
+        self.supported
+    }
+
+    pub fn no_default_features(&self) -> bool {
+        self.features.is_some()
+    }
+
+    pub fn features(&self) -> Option<&[String]> {
+        self.features.as_deref()
+    }
+}
+
+#[derive(Debug)]
+pub enum ProjectDirInvalid {
+    NormalizationFailed {
+        project_dir: String,
+        cause: util::NormalizationError,
+    },
+    OutsideOfAppRoot {
+        project_dir: String,
+        root_dir: PathBuf,
+    },
+    ContainsSpaces {
+        project_dir: String,
+    },
+}
+
+impl Display for ProjectDirInvalid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NormalizationFailed { project_dir, cause } => {
+                write!(f, "{:?} couldn't be normalized: {}", project_dir, cause)
+            }
+            Self::OutsideOfAppRoot {
+                project_dir,
+                root_dir,
+            } => write!(
+                f,
+                "{:?} is outside of the app root {:?}",
+                project_dir, root_dir,
+            ),
+            Self::ContainsSpaces { project_dir } => write!(
+                f,
+                "{:?} contains spaces, which the NDK is remarkably intolerant of",
+                project_dir
+            ),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Activity {
+    NativeActivity,
+    Custom(String),
+}
+
+impl Serialize for Activity {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        #[derive(Serialize)]
+        #[serde(rename_all = "kebab-case")]
+        struct Template<'a> {
+            class: &'a str,
+            native_activity: bool,
+        }
+        Template {
+            class: self.class(),
+            native_activity: matches!(self, Self::NativeActivity),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl Activity {
+    fn from_raw(raw: String) -> Result<Self, Error> {
+        match raw.as_str() {
+            "native-activity" => Ok(Self::NativeActivity),
+            // The app templates only have glue for `NativeActivity`, so
+            // anything built on them would fail to load.
+            "game-activity" => Err(Error::GameActivityUnsupported),
+            _ if manifest::is_qualified_name(&raw) => Ok(Self::Custom(raw)),
+            _ => Err(Error::ActivityInvalid { activity: raw }),
+        }
+    }
+
+    pub fn class(&self) -> &str {
+        match self {
+            Self::NativeActivity => "android.app.NativeActivity",
+            Self::Custom(class) => class,
+        }
+    }
+
+    pub fn has_code(&self) -> bool {
+        !matches!(self, Self::NativeActivity)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Kind {
+    Application,
+    // A Gradle library module with no activity, for embedding in an existing
+    // app. Kotlin glue can go in `java-source-dirs`, as long as the Kotlin
+    // plugin is added to `gradle-plugins`.
+    Library,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Packager {
+    Gradle,
+    // Packages with `aapt2`, `zipalign`, and `apksigner` directly, which is a
+    // lot faster, but can't build anything on the JVM side.
+    Direct,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct GradlePlugin {
+    id: String,
+    version: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ManifestSnippets {
+    #[serde(default)]
+    manifest: Vec<String>,
+    #[serde(default)]
+    application: Vec<String>,
+    #[serde(default)]
+    activity: Vec<String>,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    ProjectDirInvalid(ProjectDirInvalid),
+    RustflagsTargetInvalid {
+        target: String,
+    },
+    DefaultTargetsEmpty,
+    DefaultTargetInvalid {
+        target: String,
+    },
+    ActivityInvalid {
+        activity: String,
+    },
+    GameActivityUnsupported,
+    JavaSourceDirNormalizationFailed {
+        java_source_dir: String,
+        cause: util::NormalizationError,
+    },
+    JavaSourceDirOutsideOfAppRoot {
+        java_source_dir: String,
+    },
+    ManifestInvalid(manifest::Error),
+    DirectPackagerHasCode,
+    DirectPackagerLibrary,
+}
+
+impl Error {
+    pub fn report(&self, msg: &str) -> Report {
+        match self {
+            Self::ProjectDirInvalid(err) => Report::error(
+                msg,
+                format!("`{}.project-dir` invalid: {}", super::NAME, err),
+            ),
+            Self::RustflagsTargetInvalid { target } => Report::error(
+                msg,
+                format!(
+                    "`{}.rustflags` contains an entry for {:?}, which isn't a valid target; the possible targets are {:?}",
+                    super::NAME,
+                    target,
+                    Target::name_list(),
+                ),
+            ),
+            Self::DefaultTargetsEmpty => Report::error(
+                msg,
+                format!(
+                    "`{}.default-targets` is empty; remove it to use the default, or list at least one of {:?}",
+                    super::NAME,
+                    Target::name_list(),
+                ),
+            ),
+            Self::DefaultTargetInvalid { target } => Report::error(
+                msg,
+                format!(
+                    "`{}.default-targets` contains {:?}, which isn't a valid target; the possible targets are {:?}",
+                    super::NAME,
+                    target,
+                    Target::name_list(),
+                ),
+            ),
+            Self::ActivityInvalid { activity } => Report::error(
+                msg,
+                format!(
+                    "`{}.activity` is set to {:?}, which is neither \"native-activity\" nor a fully-qualified class name",
+                    super::NAME,
+                    activity,
+                ),
+            ),
+            Self::GameActivityUnsupported => Report::error(
+                msg,
+                format!(
+                    "`{}.activity` is set to \"game-activity\", but the app templates only provide `NativeActivity` glue, so the app would fail to load; use \"native-activity\", or the fully-qualified name of an activity class your own glue supports",
+                    super::NAME,
+                ),
+            ),
+            Self::JavaSourceDirNormalizationFailed {
+                java_source_dir,
+                cause,
+            } => Report::error(
+                msg,
+                format!(
+                    "`{}.java-source-dirs` entry {:?} couldn't be normalized: {}",
+                    super::NAME,
+                    java_source_dir,
+                    cause
+                ),
+            ),
+            Self::JavaSourceDirOutsideOfAppRoot { java_source_dir } => Report::error(
+                msg,
+                format!(
+                    "`{}.java-source-dirs` entry {:?} is outside of the app root",
+                    super::NAME,
+                    java_source_dir,
+                ),
+            ),
+            Self::DirectPackagerHasCode => Report::error(
+                msg,
+                format!(
+                    "`{}.packager` is set to \"direct\", which can't build JVM code; use \"native-activity\" with no `java-source-dirs`, `gradle-dependencies`, or `app.uniffi`, or switch back to \"gradle\"",
+                    super::NAME,
+                ),
+            ),
+            Self::DirectPackagerLibrary => Report::error(
+                msg,
+                format!(
+                    "`{}.packager` is set to \"direct\", which only builds APKs; libraries have to use \"gradle\"",
+                    super::NAME,
+                ),
+            ),
+            Self::ManifestInvalid(err) => Report::error(
+                msg,
+                format!("`{}.manifest` invalid: {}", super::NAME, err),
+            ),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Raw {
+    min_sdk_version: Option<u32>,
+    vulkan_validation: Option<bool>,
+    vulkan_validation_layers: Option<String>,
+    project_dir: Option<String>,
+    no_default_features: Option<bool>,
+    features: Option<Vec<String>>,
+    link_libs: Option<Vec<String>>,
+    rustflags: Option<BTreeMap<String, Vec<String>>>,
+    default_targets: Option<Vec<String>>,
+    bundle_shared_libs: Option<bool>,
+    strip_release_libs: Option<bool>,
+    launch_env: Option<BTreeMap<String, String>>,
+    activity: Option<String>,
+    java_source_dirs: Option<Vec<String>>,
+    gradle_dependencies: Option<Vec<String>>,
+    gradle_plugins: Option<Vec<GradlePlugin>>,
+    manifest_snippets: Option<ManifestSnippets>,
+    manifest: Option<manifest::Raw>,
+    packager: Option<Packager>,
+    kind: Option<Kind>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Config {
+    #[serde(skip_serializing)]
+    app: App,
+    min_sdk_version: u32,
+    vulkan_validation: bool,
+    vulkan_validation_layers: Option<PathBuf>,
+    project_dir: PathBuf,
+    link_libs: Vec<String>,
+    rustflags: BTreeMap<String, Vec<String>>,
+    default_targets: Vec<String>,
+    bundle_shared_libs: bool,
+    strip_release_libs: bool,
+    launch_env: BTreeMap<String, String>,
+    activity: Activity,
+    java_source_dirs: Vec<PathBuf>,
+    gradle_dependencies: Vec<String>,
+    gradle_plugins: Vec<GradlePlugin>,
+    manifest_snippets: ManifestSnippets,
+    manifest: Manifest,
+    packager: Packager,
+    kind: Kind,
+}
+
+impl Config {
+    pub fn from_raw(app: App, raw: Option<Raw>) -> Result<Self, Error> {
+        let raw = raw.unwrap_or_default();
+
+        let min_sdk_version = raw.min_sdk_version.unwrap_or_else(|| {
+            log::info!(
+                "`{}.min-sdk-version` not set; defaulting to {}",
+                super::NAME,
+                DEFAULT_MIN_SDK_VERSION
+            );
+            DEFAULT_MIN_SDK_VERSION
+        });
+
+        let vulkan_validation = raw.vulkan_validation.unwrap_or_else(|| {
+            log::info!(
+                "`{}.vulkan-validation` not set; defaulting to {}",
+                super::NAME,
+                DEFAULT_VULKAN_VALIDATION
+            );
+            DEFAULT_VULKAN_VALIDATION
+        });
+
+        let vulkan_validation_layers = raw
+            .vulkan_validation_layers
+            .map(|path| app.prefix_path(path));
+        if vulkan_validation && vulkan_validation_layers.is_none() {
+            log::info!(
+                "`{}.vulkan-validation-layers` not set; validation layers won't be bundled",
+                super::NAME
+            );
+        }
+
+        let project_dir = if let Some(project_dir) = raw.project_dir {
+            if project_dir == DEFAULT_PROJECT_DIR {
+                log::warn!(
+                    "`{}.project-dir` is set to the default value; you can remove it from your config",
+                    super::NAME
+                );
+            }
+            if util::under_root(&project_dir, app.root_dir()).map_err(|cause| {
+                Error::ProjectDirInvalid(ProjectDirInvalid::NormalizationFailed {
+                    project_dir: project_dir.clone(),
+                    cause,
+                })
+            })? {
+                if !project_dir.contains(' ') {
+                    Ok(project_dir.into())
+                } else {
+                    Err(Error::ProjectDirInvalid(
+                        ProjectDirInvalid::ContainsSpaces { project_dir },
+                    ))
+                }
+            } else {
+                Err(Error::ProjectDirInvalid(
+                    ProjectDirInvalid::OutsideOfAppRoot {
+                        project_dir,
+                        root_dir: app.root_dir().to_owned(),
+                    },
+                ))
+            }
+        } else {
+            log::info!(
+                "`{}.project-dir` not set; defaulting to {:?}",
+                super::NAME,
+                DEFAULT_PROJECT_DIR
+            );
+            Ok(DEFAULT_PROJECT_DIR.into())
+        }?;
+
+        let link_libs = raw.link_libs.unwrap_or_else(|| {
+            log::info!(
+                "`{}.link-libs` not set; defaulting to {:?}",
+                super::NAME,
+                DEFAULT_LINK_LIBS
+            );
+            DEFAULT_LINK_LIBS.iter().map(ToString::to_string).collect()
+        });
+
+        let rustflags = raw.rustflags.unwrap_or_default();
+        if let Some(target) = rustflags
+            .keys()
+            .find(|target| Target::for_name(target).is_none())
+        {
+            return Err(Error::RustflagsTargetInvalid {
+                target: target.clone(),
+            });
+        }
+
+        let default_targets = raw.default_targets.unwrap_or_else(|| {
+            log::info!(
+                "`{}.default-targets` not set; defaulting to [{:?}]",
+                super::NAME,
+                Target::DEFAULT_KEY
+            );
+            vec![Target::DEFAULT_KEY.to_owned()]
+        });
+        if default_targets.is_empty() {
+            return Err(Error::DefaultTargetsEmpty);
+        }
+        if let Some(target) = default_targets
+            .iter()
+            .find(|target| Target::for_name(target).is_none())
+        {
+            return Err(Error::DefaultTargetInvalid {
+                target: target.clone(),
+            });
+        }
+
+        let bundle_shared_libs = raw.bundle_shared_libs.unwrap_or_else(|| {
+            log::info!(
+                "`{}.bundle-shared-libs` not set; defaulting to {}",
+                super::NAME,
+                DEFAULT_BUNDLE_SHARED_LIBS
+            );
+            DEFAULT_BUNDLE_SHARED_LIBS
+        });
+
+        let strip_release_libs = raw.strip_release_libs.unwrap_or_else(|| {
+            log::info!(
+                "`{}.strip-release-libs` not set; defaulting to {}",
+                super::NAME,
+                DEFAULT_STRIP_RELEASE_LIBS
+            );
+            DEFAULT_STRIP_RELEASE_LIBS
+        });
+
+        let launch_env = raw.launch_env.unwrap_or_else(|| {
+            log::info!(
+                "`{}.launch-env` not set; defaulting to {:?}",
+                super::NAME,
+                DEFAULT_LAUNCH_ENV
+            );
+            DEFAULT_LAUNCH_ENV
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect()
+        });
+
+        let activity = if let Some(activity) = raw.activity {
+            Activity::from_raw(activity)?
+        } else {
+            log::info!(
+                "`{}.activity` not set; defaulting to {:?}",
+                super::NAME,
+                DEFAULT_ACTIVITY
+            );
+            Activity::NativeActivity
+        };
+
+        let java_source_dirs = raw
+            .java_source_dirs
+            .unwrap_or_default()
+            .into_iter()
+            .map(|java_source_dir| {
+                if util::under_root(&java_source_dir, app.root_dir()).map_err(|cause| {
+                    Error::JavaSourceDirNormalizationFailed {
+                        java_source_dir: java_source_dir.clone(),
+                        cause,
+                    }
+                })? {
+                    Ok(java_source_dir.into())
+                } else {
+                    Err(Error::JavaSourceDirOutsideOfAppRoot { java_source_dir })
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let packager = raw.packager.unwrap_or_else(|| {
+            log::info!(
+                "`{}.packager` not set; defaulting to \"gradle\"",
+                super::NAME
+            );
+            DEFAULT_PACKAGER
+        });
+
+        let kind = raw.kind.unwrap_or_else(|| {
+            log::info!(
+                "`{}.kind` not set; defaulting to \"application\"",
+                super::NAME
+            );
+            DEFAULT_KIND
+        });
+
+        let config = Self {
+            app,
+            min_sdk_version,
+            vulkan_validation,
+            vulkan_validation_layers,
+            project_dir,
+            link_libs,
+            rustflags,
+            default_targets,
+            bundle_shared_libs,
+            strip_release_libs,
+            launch_env,
+            activity,
+            java_source_dirs,
+            gradle_dependencies: raw.gradle_dependencies.unwrap_or_default(),
+            gradle_plugins: raw.gradle_plugins.unwrap_or_default(),
+            manifest_snippets: raw.manifest_snippets.unwrap_or_default(),
+            manifest: Manifest::from_raw(raw.manifest).map_err(Error::ManifestInvalid)?,
+            packager,
+            kind,
+        };
+        if config.packager == Packager::Direct && config.kind == Kind::Library {
+            Err(Error::DirectPackagerLibrary)
+        } else if config.packager == Packager::Direct && config.has_code() {
+            Err(Error::DirectPackagerHasCode)
+        } else {
+            Ok(config)
+        }
+    }
+
+    pub fn app(&self) -> &App {
+        &self.app
+    }
+
+    pub fn package_name(&self) -> String {
+        format!("{}.{}", self.app.reverse_domain(), self.app.name_snake())
+    }
+
+    pub fn min_sdk_version(&self) -> u32 {
+        self.min_sdk_version
+    }
+
+    pub fn vulkan_validation_layers(&self) -> Option<&Path> {
+        self.vulkan_validation_layers
+            .as_deref()
+            .filter(|_| self.vulkan_validation)
+    }
+
+    pub fn project_dir(&self) -> PathBuf {
+        self.app
+            .prefix_path(&self.project_dir)
+            .join(self.app().name())
+    }
+
+    pub fn project_dir_exists(&self) -> bool {
+        self.project_dir().is_dir()
+    }
+
+    pub fn link_libs(&self) -> &[String] {
+        &self.link_libs
+    }
+
+    pub fn rustflags(&self, target_name: &str) -> &[String] {
+        self.rustflags
+            .get(target_name)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    pub fn default_targets(&self) -> &[String] {
+        &self.default_targets
+    }
+
+    pub fn bundle_shared_libs(&self) -> bool {
+        self.bundle_shared_libs
+    }
+
+    pub fn strip_release_libs(&self) -> bool {
+        self.strip_release_libs
+    }
+
+    pub fn launch_env(&self) -> &BTreeMap<String, String> {
+        &self.launch_env
+    }
+
+    pub fn activity(&self) -> &Activity {
+        &self.activity
+    }
+
+    pub fn java_source_dirs(&self) -> impl Iterator<Item = PathBuf> + '_ {
+        self.java_source_dirs
+            .iter()
+            .map(move |dir| self.app.prefix_path(dir))
+    }
+
+    pub fn gradle_dependencies(&self) -> &[String] {
+        &self.gradle_dependencies
+    }
+
+    pub fn gradle_plugins(&self) -> &[GradlePlugin] {
+        &self.gradle_plugins
+    }
+
+    pub fn manifest_snippets(&self) -> &ManifestSnippets {
+        &self.manifest_snippets
+    }
+
+    pub fn packager(&self) -> Packager {
+        self.packager
+    }
+
+    pub fn kind(&self) -> Kind {
+        self.kind
+    }
+
+    // Without this, the APK won't contain any dex files, so anything on the
+    // JVM side (including dependencies) would fail to load.
+    pub fn has_code(&self) -> bool {
+        self.activity.has_code()
+            || !self.java_source_dirs.is_empty()
+            || !self.gradle_dependencies.is_empty()
+            || self.app.uniffi()
+    }
+}
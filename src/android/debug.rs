@@ -0,0 +1,358 @@
+use super::{
+    config::{Config, Metadata},
+    device::{Device, RunError, RunOptions},
+    env::Env,
+    ndk::{self, LlvmTool},
+};
+use crate::{
+    env::ExplicitEnv as _,
+    opts::{self, NoiseLevel, Profile},
+    util::{
+        self,
+        cli::{Report, Reportable},
+    },
+};
+use serde_json::json;
+use std::{
+    fmt::Display,
+    fs, io,
+    path::{Path, PathBuf},
+    thread,
+    time::Duration,
+};
+use structopt::StructOpt;
+
+// `lldb-server` has to run as the app's user to attach to it, so it's copied
+// into the app's data dir, which is also where `run-as` starts out.
+static STAGING_PATH: &str = "/data/local/tmp/lldb-server";
+static SERVER_NAME: &str = "lldb-server";
+// The VS Code config that `init` generates uses these too.
+pub(super) static DEFAULT_PORT: &str = "5039";
+pub(super) static DEFAULT_JDWP_PORT: &str = "8700";
+
+#[derive(Debug)]
+pub enum Error {
+    RunFailed(RunError),
+    PidMissing { package: String },
+    MissingTool(ndk::MissingToolError),
+    JdbCheckFailed(bossy::Error),
+    JdbMissing,
+    PushFailed(bossy::Error),
+    ServerFailed(bossy::Error),
+    ForwardFailed { port: u16, cause: bossy::Error },
+    ScriptWriteFailed { path: PathBuf, cause: io::Error },
+    LldbFailed(bossy::Error),
+}
+
+impl Reportable for Error {
+    fn report(&self) -> Report {
+        match self {
+            Self::RunFailed(err) => err.report(),
+            Self::PidMissing { package } => Report::error(
+                "Failed to attach debugger",
+                format!("{} doesn't seem to be running", package),
+            ),
+            Self::MissingTool(err) => Report::action_request(
+                "Failed to locate required debugging tool",
+                format!("`lldb` and `lldb-server` ship with NDK r23 and later, so you might need to update your NDK: {}", err),
+            ),
+            Self::JdbCheckFailed(err) => {
+                Report::error("Failed to check for presence of `jdb` command", err)
+            }
+            Self::JdbMissing => Report::action_request(
+                "`jdb` command not found",
+                "The app waits for a Java debugger before it starts, and `jdb` is what we use to release it once LLDB is attached. It ships with the JDK, so make sure the JDK's `bin` dir is on your `PATH`.",
+            ),
+            Self::PushFailed(err) => Report::action_request(
+                "Failed to push `lldb-server` to device",
+                format!("`run-as` only works for debuggable builds, so make sure you're debugging a debug build: {}", err),
+            ),
+            Self::ServerFailed(err) => Report::error("Failed to start `lldb-server`", err),
+            Self::ForwardFailed { port, cause } => {
+                Report::error(format!("Failed to forward port {}", port), cause)
+            }
+            Self::ScriptWriteFailed { path, cause } => Report::error(
+                format!("Failed to write LLDB commands to {:?}", path),
+                cause,
+            ),
+            Self::LldbFailed(err) => Report::error("`lldb` failed", err),
+        }
+    }
+}
+
+#[derive(Clone, Debug, StructOpt)]
+pub struct Options {
+    #[structopt(long = "no-build", help = "Debug the installed APK without rebuilding")]
+    pub no_build: bool,
+    #[structopt(
+        long = "vscode",
+        help = "Print a CodeLLDB launch configuration instead of starting `lldb`"
+    )]
+    pub vscode: bool,
+    #[structopt(
+        long = "port",
+        help = "Local port for `lldb-server`; the next port is used for the debug session itself",
+        default_value = DEFAULT_PORT
+    )]
+    pub port: u16,
+    #[structopt(
+        long = "jdwp-port",
+        help = "Local port for the Java debugger, which releases the app once LLDB is attached",
+        default_value = DEFAULT_JDWP_PORT
+    )]
+    pub jdwp_port: u16,
+}
+
+// These are the names LLDB uses for its runtime dirs, which don't quite match
+// any of the names we already have for each ABI.
+fn lldb_arch(abi: &str) -> &'static str {
+    match abi {
+        "arm64-v8a" => "aarch64",
+        "armeabi-v7a" => "arm",
+        "x86" => "i386",
+        "x86_64" => "x86_64",
+        _ => panic!("developer error: no LLDB arch for ABI {:?}", abi),
+    }
+}
+
+// The app is launched with `-D`, so it'll sit on the "Waiting For Debugger"
+// dialog until something speaks JDWP to it. Connecting `jdb` is enough, but we
+// only want to do that once LLDB is attached, or we'd miss anything that
+// happens during startup. Its errors still go to the terminal, in case it
+// can't connect.
+pub(super) fn release_command(jdwp_port: impl Display) -> String {
+    format!(
+        "script import subprocess; subprocess.Popen(['jdb', '-connect', 'com.sun.jdi.SocketAttach:hostname=localhost,port={}'], stdin=subprocess.PIPE, stdout=subprocess.DEVNULL)",
+        jdwp_port
+    )
+}
+
+// The unstripped libs in the target dir have all the debug info, so LLDB
+// should prefer them over the ones it pulls from the device.
+pub(super) fn init_commands(
+    sysroot: &Path,
+    port: impl Display,
+    symbol_dirs: &[&Path],
+) -> Vec<String> {
+    vec![
+        format!(
+            "platform select remote-android --sysroot {}",
+            util::shell_quote(sysroot)
+        ),
+        format!("platform connect connect://localhost:{}", port),
+    ]
+    .into_iter()
+    .chain(symbol_dirs.iter().map(|dir| {
+        format!(
+            "settings append target.exec-search-paths {}",
+            util::shell_quote(dir)
+        )
+    }))
+    .collect()
+}
+
+// Undoes everything `attach` sets up on the device, no matter how it exits.
+struct Session<'a, 'b> {
+    device: &'a Device<'b>,
+    env: &'a Env,
+    package: &'a str,
+    server: Option<bossy::Handle>,
+    forwarded: Vec<u16>,
+}
+
+impl Session<'_, '_> {
+    fn forward(&mut self, port: u16, remote: &str) -> Result<(), Error> {
+        self.device
+            .adb(self.env)
+            .with_args(&["forward", &format!("tcp:{}", port), remote])
+            .run_and_wait()
+            .map_err(|cause| Error::ForwardFailed { port, cause })?;
+        self.forwarded.push(port);
+        Ok(())
+    }
+}
+
+impl Drop for Session<'_, '_> {
+    fn drop(&mut self) {
+        // Killing `adb shell` doesn't necessarily kill what it's running, so
+        // this is needed even if the server's handle already returned.
+        if let Err(err) = self
+            .device
+            .adb(self.env)
+            .with_args(&["shell", "run-as", self.package, "pkill", SERVER_NAME])
+            .run_and_wait()
+        {
+            log::info!("failed to stop `lldb-server`: {}", err);
+        }
+        if let Some(mut server) = self.server.take() {
+            if let Err(err) = server.wait() {
+                log::info!("`lldb-server` exited: {}", err);
+            }
+        }
+        for port in &self.forwarded {
+            if let Err(err) = self
+                .device
+                .adb(self.env)
+                .with_args(&["forward", "--remove", &format!("tcp:{}", port)])
+                .run_and_wait()
+            {
+                log::info!("failed to remove forward for port {}: {}", port, err);
+            }
+        }
+    }
+}
+
+pub fn attach(
+    device: &Device<'_>,
+    config: &Config,
+    metadata: &Metadata,
+    env: &Env,
+    noise_level: NoiseLevel,
+    non_interactive: opts::NonInteractive,
+    profile: Profile,
+    options: &Options,
+) -> Result<(), Error> {
+    let package = config.package_name();
+    let target = device.target();
+    let server = env
+        .ndk
+        .lldb_server_path(lldb_arch(target.abi))
+        .map_err(Error::MissingTool)?;
+    let lldb = env
+        .ndk
+        .llvm_tool_path(LlvmTool::Lldb)
+        .map_err(Error::MissingTool)?;
+    let sysroot = env.ndk.sysroot().map_err(Error::MissingTool)?;
+    // Without `jdb`, the app would be stuck on "Waiting For Debugger" forever.
+    if !util::command_present("jdb").map_err(Error::JdbCheckFailed)? {
+        return Err(Error::JdbMissing);
+    }
+
+    device
+        .run(
+            config,
+            metadata,
+            env,
+            noise_level,
+            non_interactive,
+            profile,
+            &RunOptions {
+                no_build: options.no_build,
+                no_logcat: true,
+                wait_for_debugger: true,
+                ..Default::default()
+            },
+        )
+        .map_err(Error::RunFailed)?;
+    let pid = device.pid(env, &package).ok_or_else(|| Error::PidMissing {
+        package: package.clone(),
+    })?;
+
+    println!("Starting `lldb-server` on device...");
+    device
+        .adb(env)
+        .with_arg("push")
+        .with_arg(&server)
+        .with_arg(STAGING_PATH)
+        .run_and_wait()
+        .and_then(|_| {
+            device
+                .adb(env)
+                .with_args(&[
+                    "shell",
+                    "run-as",
+                    &package,
+                    "sh",
+                    "-c",
+                    &util::shell_quote(format!(
+                        "cp {} {} && chmod 700 {}",
+                        STAGING_PATH, SERVER_NAME, SERVER_NAME
+                    )),
+                ])
+                .run_and_wait()
+        })
+        .map_err(Error::PushFailed)?;
+    let gdbserver_port = options.port + 1;
+    // Ctrl-C is how you stop debugging, so we have to outlive it to clean up.
+    if let Err(err) = ctrlc::set_handler(|| ()) {
+        log::warn!("failed to set Ctrl-C handler: {}", err);
+    }
+    let server = device
+        .adb(env)
+        .with_args(&["shell", "run-as", &package])
+        .with_arg(format!("./{}", SERVER_NAME))
+        .with_args(&["platform", "--server", "--listen"])
+        .with_arg(util::shell_quote(format!("*:{}", options.port)))
+        .with_arg("--gdbserver-port")
+        .with_arg(gdbserver_port.to_string())
+        .run()
+        .map_err(Error::ServerFailed)?;
+    let mut session = Session {
+        device,
+        env,
+        package: &package,
+        server: Some(server),
+        forwarded: Vec::new(),
+    };
+    // There's no way to tell when it's listening, but it doesn't take long.
+    thread::sleep(Duration::from_secs(1));
+    session.forward(options.port, &format!("tcp:{}", options.port))?;
+    session.forward(gdbserver_port, &format!("tcp:{}", gdbserver_port))?;
+    session.forward(options.jdwp_port, &format!("jdwp:{}", pid))?;
+
+    let lib_path = target.lib_path(config, profile);
+    let symbol_dir = lib_path
+        .parent()
+        .expect("developer error: lib path had no parent");
+    let init_commands = init_commands(&sysroot, options.port, &[symbol_dir]);
+
+    if options.vscode {
+        let launch = json!({
+            "type": "lldb",
+            "request": "attach",
+            "name": format!("Debug {} on {}", package, device),
+            "pid": pid,
+            "initCommands": init_commands,
+            "postRunCommands": [release_command(options.jdwp_port)],
+        });
+        println!(
+            "Add this to the `configurations` in `.vscode/launch.json`, then start debugging:"
+        );
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&launch).expect("developer error: invalid JSON")
+        );
+        println!("`lldb-server` will keep running until you press Ctrl-C.");
+        if let Some(mut server) = session.server.take() {
+            if let Err(err) = server.wait() {
+                log::info!("`lldb-server` exited: {}", err);
+            }
+        }
+        Ok(())
+    } else {
+        let script = util::temp_dir().join(format!("lldb-{}.txt", package));
+        let commands = init_commands
+            .into_iter()
+            .chain(vec![
+                format!("process attach --pid {}", pid),
+                release_command(options.jdwp_port),
+                "continue".to_owned(),
+            ])
+            .collect::<Vec<_>>()
+            .join("\n");
+        fs::create_dir_all(util::temp_dir())
+            .and_then(|()| fs::write(&script, commands))
+            .map_err(|cause| Error::ScriptWriteFailed {
+                path: script.clone(),
+                cause,
+            })?;
+        bossy::Command::pure(&lldb)
+            .with_env_vars(env.explicit_env())
+            .with_env_var("ANDROID_SERIAL", device.serial_no())
+            .with_arg("--source")
+            .with_arg(&script)
+            .run_and_wait()
+            .map(|_| ())
+            .map_err(Error::LldbFailed)
+    }
+}
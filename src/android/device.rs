@@ -1,23 +1,44 @@
 use super::{
-    adb,
-    config::Config,
+    adb, apk,
+    config::{Config, Metadata, Packager},
     env::Env,
+    logcat,
+    stacktrace::{self, Crash, StacktraceError},
     target::{BuildError, Target},
 };
 use crate::{
     env::ExplicitEnv as _,
-    opts::{NoiseLevel, Profile},
+    opts::{self, ForceColor, NoiseLevel, OutputFormat, Profile, StripSymbols},
     util::{
         self,
-        cli::{Report, Reportable},
+        cli::{self, Report, Reportable, TextWrapper},
+        prompt,
+        watch::{self, Watcher},
     },
 };
+use once_cell_regex::regex;
+use sha1::{Digest as _, Sha1};
 use std::{
+    collections::BTreeMap,
     fmt::{self, Display},
-    io,
+    fs, io,
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
+use structopt::StructOpt;
 
-fn gradlew(config: &Config, env: &Env) -> bossy::Command {
+// The app glue reads this back and puts it into the environment on startup.
+// It lives in the app's own files dir, so each app only ever sees its own.
+static LAUNCH_ENV_FILE_NAME: &str = "cargo-mobile-env";
+// `sync-assets` and watch mode push changed assets to the device instead of
+// reinstalling, and this env var tells the app where they ended up. It's read
+// by `asset_path` in the app glue.
+static ASSET_DIR_ENV: &str = "SYNCED_ASSET_DIR";
+// Lists the hash of every synced asset, so we only push what changed.
+static ASSET_MANIFEST_NAME: &str = ".cargo-mobile-assets";
+
+pub(super) fn gradlew(config: &Config, env: &Env) -> bossy::Command {
     let gradlew_path = config.project_dir().join("gradlew");
     bossy::Command::pure(&gradlew_path)
         .with_env_vars(env.explicit_env())
@@ -25,11 +46,20 @@ fn gradlew(config: &Config, env: &Env) -> bossy::Command {
         .with_arg(config.project_dir())
 }
 
+pub(super) fn gradle_log_level(noise_level: NoiseLevel) -> &'static str {
+    match noise_level {
+        NoiseLevel::Polite => "--warn",
+        NoiseLevel::LoudAndProud => "--info",
+        NoiseLevel::FranklyQuitePedantic => "--debug",
+    }
+}
+
 #[derive(Debug)]
 pub enum ApkBuildError {
     LibSymlinkCleaningFailed(io::Error),
     LibBuildFailed(BuildError),
     AssembleFailed(bossy::Error),
+    PackageFailed(apk::Error),
 }
 
 impl Reportable for ApkBuildError {
@@ -40,30 +70,262 @@ impl Reportable for ApkBuildError {
             }
             Self::LibBuildFailed(err) => err.report(),
             Self::AssembleFailed(err) => Report::error("Failed to assemble APK", err),
+            Self::PackageFailed(err) => err.report(),
         }
     }
 }
 
 #[derive(Debug)]
 pub enum ApkInstallError {
+    HashFailed { path: PathBuf, cause: io::Error },
     InstallFailed(bossy::Error),
+    InstallRejected { code: String, reason: String },
+    PromptFailed(io::Error),
 }
 
 impl Reportable for ApkInstallError {
     fn report(&self) -> Report {
         match self {
+            Self::HashFailed { path, cause } => {
+                Report::error(format!("Failed to hash APK at {:?}", path), cause)
+            }
             Self::InstallFailed(err) => Report::error("Failed to install APK", err),
+            Self::InstallRejected { code, reason } => {
+                let msg = format!("Failed to install APK ({})", code);
+                match code.as_str() {
+                    "INSTALL_FAILED_UPDATE_INCOMPATIBLE" => Report::action_request(msg, "The installed copy of the app was signed with a different key. Run again with `--uninstall-first` to replace it (this deletes the app's data!)"),
+                    "INSTALL_FAILED_VERSION_DOWNGRADE" => Report::action_request(msg, "The installed copy of the app has a higher version code. Run again with `--uninstall-first` to replace it (this deletes the app's data!)"),
+                    "INSTALL_FAILED_INSUFFICIENT_STORAGE" => Report::action_request(msg, "The device is out of storage space. Free some up and try again."),
+                    "INSTALL_FAILED_NO_MATCHING_ABIS" => Report::error(msg, "The APK doesn't contain libs for any of the device's ABIs. Make sure you're building for the right target."),
+                    "INSTALL_FAILED_OLDER_SDK" => Report::error(msg, "The device's Android version is older than `android.min-sdk-version`."),
+                    "INSTALL_FAILED_USER_RESTRICTED" => Report::action_request(msg, "The install was blocked on the device. Some devices require you to enable \"Install via USB\" in developer options, or to accept a prompt on screen."),
+                    "INSTALL_PARSE_FAILED_NO_CERTIFICATES" => Report::error(msg, "The APK isn't signed. Release builds need to be signed before they can be installed."),
+                    _ => Report::error(msg, reason),
+                }
+            }
+            Self::PromptFailed(err) => Report::error("Failed to prompt for uninstall", err),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum LaunchEnvError {
+    WriteFailed { path: PathBuf, cause: io::Error },
+    PushFailed(bossy::Error),
+    ChmodFailed(bossy::Error),
+}
+
+impl Reportable for LaunchEnvError {
+    fn report(&self) -> Report {
+        match self {
+            Self::WriteFailed { path, cause } => {
+                Report::error(format!("Failed to write launch env to {:?}", path), cause)
+            }
+            Self::PushFailed(err) => Report::error("Failed to push launch env to device", err),
+            Self::ChmodFailed(err) => {
+                Report::error("Failed to make launch env readable by the app", err)
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum AssetSyncError {
+    ReadFailed { path: PathBuf, cause: io::Error },
+    ManifestWriteFailed { path: PathBuf, cause: io::Error },
+    RemoveFailed(bossy::Error),
+    PushFailed { path: PathBuf, cause: bossy::Error },
+    ChmodFailed(bossy::Error),
+    LaunchEnvFailed(LaunchEnvError),
+    RestartFailed(bossy::Error),
+}
+
+impl Reportable for AssetSyncError {
+    fn report(&self) -> Report {
+        match self {
+            Self::ReadFailed { path, cause } => {
+                Report::error(format!("Failed to read asset {:?}", path), cause)
+            }
+            Self::ManifestWriteFailed { path, cause } => Report::error(
+                format!("Failed to write asset manifest to {:?}", path),
+                cause,
+            ),
+            Self::RemoveFailed(err) => {
+                Report::error("Failed to remove deleted assets from device", err)
+            }
+            Self::PushFailed { path, cause } => {
+                Report::error(format!("Failed to push asset {:?} to device", path), cause)
+            }
+            Self::ChmodFailed(err) => {
+                Report::error("Failed to make synced assets readable by the app", err)
+            }
+            Self::LaunchEnvFailed(err) => err.report(),
+            Self::RestartFailed(err) => Report::error("Failed to restart app on device", err),
+        }
+    }
+}
+
+// Keys are `/`-separated paths relative to `root`, so they can be used
+// as-is on the device. Symlinks are followed, since the asset dir itself is
+// often one.
+fn hash_assets(
+    root: &Path,
+    dir: &Path,
+    hashes: &mut BTreeMap<String, String>,
+) -> Result<(), AssetSyncError> {
+    let entries = fs::read_dir(dir).map_err(|cause| AssetSyncError::ReadFailed {
+        path: dir.to_owned(),
+        cause,
+    })?;
+    for entry in entries {
+        let path = entry
+            .map_err(|cause| AssetSyncError::ReadFailed {
+                path: dir.to_owned(),
+                cause,
+            })?
+            .path();
+        if path.is_dir() {
+            hash_assets(root, &path, hashes)?;
+        } else {
+            let bytes = fs::read(&path).map_err(|cause| AssetSyncError::ReadFailed {
+                path: path.clone(),
+                cause,
+            })?;
+            let rel = path
+                .strip_prefix(root)
+                .expect("developer error: asset wasn't under asset dir")
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            hashes.insert(rel, format!("{:x}", Sha1::digest(&bytes)));
         }
     }
+    Ok(())
+}
+
+#[derive(Debug)]
+pub enum ToolError {
+    ScreenshotFailed(bossy::Error),
+    RecordFailed(bossy::Error),
+    ShellFailed(bossy::Error),
+    PushFailed { path: PathBuf, cause: bossy::Error },
+    PullFailed { path: String, cause: bossy::Error },
+    WriteFailed { path: PathBuf, cause: io::Error },
+    UninstallFailed(bossy::Error),
+    ClearDataFailed(bossy::Error),
+}
+
+impl Reportable for ToolError {
+    fn report(&self) -> Report {
+        match self {
+            Self::ScreenshotFailed(err) => Report::error("Failed to take screenshot", err),
+            Self::RecordFailed(err) => Report::error("Failed to record screen", err),
+            Self::ShellFailed(err) => Report::error("`adb shell` failed", err),
+            Self::PushFailed { path, cause } => Report::action_request(
+                format!("Failed to push {:?} to app data dir", path),
+                format!("`run-as` only works for debuggable builds, so make sure a debug build is installed: {}", cause),
+            ),
+            Self::PullFailed { path, cause } => Report::action_request(
+                format!("Failed to pull {:?} from app data dir", path),
+                format!("`run-as` only works for debuggable builds, so make sure a debug build is installed: {}", cause),
+            ),
+            Self::WriteFailed { path, cause } => {
+                Report::error(format!("Failed to write {:?}", path), cause)
+            }
+            Self::UninstallFailed(err) => Report::error("Failed to uninstall app", err),
+            Self::ClearDataFailed(err) => Report::error("Failed to clear app data", err),
+        }
+    }
+}
+
+// Used when no output path is given, so repeated captures don't clobber each
+// other.
+fn capture_path(package: &str, ext: &str) -> PathBuf {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default();
+    PathBuf::from(format!("{}-{}.{}", package, secs, ext))
+}
+
+fn app_files_dir(package: &str) -> String {
+    format!("/sdcard/Android/data/{}/files", package)
+}
+
+fn synced_asset_dir(package: &str) -> String {
+    format!("{}/assets", app_files_dir(package))
+}
+
+fn launch_env_path(package: &str) -> String {
+    format!("{}/{}", app_files_dir(package), LAUNCH_ENV_FILE_NAME)
+}
+
+#[derive(Clone, Debug, Default, StructOpt)]
+pub struct RunOptions {
+    #[structopt(
+        long = "no-build",
+        help = "Install the existing APK without rebuilding"
+    )]
+    pub no_build: bool,
+    #[structopt(
+        long = "no-logcat",
+        help = "Exit after launching instead of showing logs"
+    )]
+    pub no_logcat: bool,
+    #[structopt(long = "clear-data", help = "Clear the app's data before launching")]
+    pub clear_data: bool,
+    #[structopt(long = "uninstall-first", help = "Uninstall the app before installing")]
+    pub uninstall_first: bool,
+    #[structopt(
+        long = "launch-arg",
+        help = "Pass `KEY=VALUE` to the app as a string intent extra",
+        number_of_values = 1,
+        parse(try_from_str = cli::parse_key_value)
+    )]
+    pub launch_args: Vec<(String, String)>,
+    #[structopt(
+        long = "env",
+        help = "Set `KEY=VALUE` in the app's environment, overriding `android.launch-env`",
+        number_of_values = 1,
+        parse(try_from_str = cli::parse_key_value)
+    )]
+    pub env: Vec<(String, String)>,
+    #[structopt(
+        long = "wait-for-debugger",
+        help = "Have the app wait for a debugger to attach on launch"
+    )]
+    pub wait_for_debugger: bool,
+    #[structopt(
+        long = "stop-on-exit",
+        help = "Force-stop the app when logging is ended with Ctrl-C"
+    )]
+    pub stop_on_exit: bool,
+    #[structopt(
+        long = "reverse",
+        help = "Forward this port on the device to the same port on this machine",
+        number_of_values = 1
+    )]
+    pub reverse: Vec<u16>,
+    #[structopt(
+        long = "watch",
+        help = "Rebuild and redeploy whenever sources or assets change"
+    )]
+    pub watch: bool,
 }
 
 #[derive(Debug)]
 pub enum RunError {
     ApkBuildFailed(ApkBuildError),
     ApkInstallFailed(ApkInstallError),
+    ReverseFailed { port: u16, cause: bossy::Error },
+    ClearDataFailed(bossy::Error),
+    LaunchEnvFailed(LaunchEnvError),
     StartFailed(bossy::Error),
     WakeScreenFailed(bossy::Error),
-    LogcatFailed(bossy::Error),
+    AssetSyncFailed(AssetSyncError),
+    LogcatFailed(logcat::Error),
+    StopFailed(bossy::Error),
 }
 
 impl Reportable for RunError {
@@ -71,22 +333,16 @@ impl Reportable for RunError {
         match self {
             Self::ApkBuildFailed(err) => err.report(),
             Self::ApkInstallFailed(err) => err.report(),
+            Self::ReverseFailed { port, cause } => {
+                Report::error(format!("Failed to reverse-forward port {}", port), cause)
+            }
+            Self::ClearDataFailed(err) => Report::error("Failed to clear app data", err),
+            Self::LaunchEnvFailed(err) => err.report(),
             Self::StartFailed(err) => Report::error("Failed to start app on device", err),
             Self::WakeScreenFailed(err) => Report::error("Failed to wake device screen", err),
-            Self::LogcatFailed(err) => Report::error("Failed to log output", err),
-        }
-    }
-}
-
-#[derive(Debug)]
-pub enum StacktraceError {
-    PipeFailed(util::PipeError),
-}
-
-impl Reportable for StacktraceError {
-    fn report(&self) -> Report {
-        match self {
-            Self::PipeFailed(err) => Report::error("Failed to pipe stacktrace output", err),
+            Self::AssetSyncFailed(err) => err.report(),
+            Self::LogcatFailed(err) => err.report(),
+            Self::StopFailed(err) => Report::error("Failed to stop app on device", err),
         }
     }
 }
@@ -128,56 +384,180 @@ impl<'a> Device<'a> {
         self.target
     }
 
-    fn adb(&self, env: &Env) -> bossy::Command {
+    pub(super) fn serial_no(&self) -> &str {
+        &self.serial_no
+    }
+
+    pub(super) fn adb(&self, env: &Env) -> bossy::Command {
         adb::adb(env, &self.serial_no)
     }
 
     fn build_apk(
         &self,
         config: &Config,
+        metadata: &Metadata,
         env: &Env,
         noise_level: NoiseLevel,
         profile: Profile,
     ) -> Result<(), ApkBuildError> {
         use heck::CamelCase as _;
         Target::clean_jnilibs(config).map_err(ApkBuildError::LibSymlinkCleaningFailed)?;
+        if config.packager() == Packager::Direct {
+            self.target
+                .build(
+                    config,
+                    metadata,
+                    env,
+                    noise_level,
+                    ForceColor::Yes,
+                    profile,
+                    StripSymbols::No,
+                )
+                .map_err(ApkBuildError::LibBuildFailed)?;
+            return apk::build(config, env, self.target, profile)
+                .map_err(ApkBuildError::PackageFailed);
+        }
         let flavor = self.target.arch.to_camel_case();
         let build_ty = profile.as_str().to_camel_case();
         gradlew(config, env)
             .with_arg(format!("assemble{}{}", flavor, build_ty))
-            .with_arg(match noise_level {
-                NoiseLevel::Polite => "--warn",
-                NoiseLevel::LoudAndProud => "--info",
-                NoiseLevel::FranklyQuitePedantic => "--debug",
-            })
+            .with_arg(gradle_log_level(noise_level))
             .run_and_wait()
             .map_err(ApkBuildError::AssembleFailed)?;
         Ok(())
     }
 
+    // Anything going wrong here just means we install anyway.
+    fn installed_apk_matches(&self, env: &Env, package: &str, hash: &str) -> bool {
+        let installed_path = match self
+            .adb(env)
+            .with_args(&["shell", "pm", "path", package])
+            .run_and_wait_for_output()
+        {
+            Ok(output) => String::from_utf8_lossy(output.stdout())
+                .lines()
+                .filter_map(|line| line.trim().strip_prefix("package:"))
+                .find(|path| path.ends_with("/base.apk"))
+                .map(ToOwned::to_owned),
+            Err(err) => {
+                log::info!("failed to find installed APK for {}: {}", package, err);
+                None
+            }
+        };
+        installed_path
+            .and_then(|path| {
+                self.adb(env)
+                    .with_args(&["shell", "sha1sum", &path])
+                    .run_and_wait_for_output()
+                    .map_err(|err| log::info!("failed to hash installed APK: {}", err))
+                    .ok()
+            })
+            .map_or(false, |output| {
+                String::from_utf8_lossy(output.stdout())
+                    .split_whitespace()
+                    .next()
+                    .map_or(false, |installed_hash| installed_hash == hash)
+            })
+    }
+
+    // Devices that support it get a streamed install, which skips copying the
+    // APK to a temporary location first. If there's a v4 signature, we can go
+    // one better and do an incremental install.
+    fn install_mode(&self, env: &Env, apk_path: &Path) -> Option<&'static str> {
+        let sdk = adb::get_prop(env, &self.serial_no, "ro.build.version.sdk")
+            .map_err(|err| log::info!("failed to get device SDK version: {}", err))
+            .ok()?
+            .parse::<u32>()
+            .ok()?;
+        let mut idsig_path = apk_path.as_os_str().to_owned();
+        idsig_path.push(".idsig");
+        if sdk >= 30 && Path::new(&idsig_path).is_file() {
+            Some("--incremental")
+        } else if sdk >= 24 {
+            Some("--streaming")
+        } else {
+            None
+        }
+    }
+
+    fn try_install_apk(&self, env: &Env, apk_path: &Path) -> Result<(), ApkInstallError> {
+        let mut command = self.adb(env);
+        command.add_args(&["install", "-r"]);
+        if let Some(mode) = self.install_mode(env, apk_path) {
+            command.add_arg(mode);
+        }
+        // Older versions of `adb` print failures to stdout and still exit
+        // successfully, so we have to check both.
+        let output = match command.with_arg(apk_path).run_and_wait_for_output() {
+            Ok(output) => String::from_utf8_lossy(output.stdout()).into_owned(),
+            Err(err) => err
+                .stderr_str()
+                .and_then(Result::ok)
+                .filter(|stderr| stderr.contains("Failure ["))
+                .map(ToOwned::to_owned)
+                .ok_or(ApkInstallError::InstallFailed(err))?,
+        };
+        if let Some(caps) = regex!(r"Failure \[([A-Z_]+)(?::\s*(.*?))?\]").captures(&output) {
+            Err(ApkInstallError::InstallRejected {
+                code: caps[1].to_owned(),
+                reason: caps
+                    .get(2)
+                    .map(|reason| reason.as_str().to_owned())
+                    .unwrap_or_default(),
+            })
+        } else {
+            Ok(())
+        }
+    }
+
     fn install_apk(
         &self,
         config: &Config,
         env: &Env,
+        non_interactive: opts::NonInteractive,
         profile: Profile,
     ) -> Result<(), ApkInstallError> {
-        let flavor = self.target.arch;
-        let build_ty = profile.as_str();
-        let apk_suffix = match profile {
-            Profile::Debug => build_ty,
-            // TODO: how to handle signed APKs?
-            Profile::Release => "release-unsigned",
-        };
-        let apk_path = config.project_dir().join(format!(
-            "app/build/outputs/apk/{}/{}/app-{}-{}.apk",
-            flavor, build_ty, flavor, apk_suffix
-        ));
-        self.adb(env)
-            .with_arg("install")
-            .with_arg(apk_path)
-            .run_and_wait()
-            .map_err(ApkInstallError::InstallFailed)?;
-        Ok(())
+        let apk_path = self.target.apk_path(config, profile);
+        let package = config.package_name();
+        let bytes = fs::read(&apk_path).map_err(|cause| ApkInstallError::HashFailed {
+            path: apk_path.clone(),
+            cause,
+        })?;
+        let hash = format!("{:x}", Sha1::digest(&bytes));
+        if self.installed_apk_matches(env, &package, &hash) {
+            println!(
+                "APK is unchanged from what's installed on {}; skipping install",
+                self
+            );
+            return Ok(());
+        }
+        match self.try_install_apk(env, &apk_path) {
+            Err(ApkInstallError::InstallRejected { code, .. })
+                if code == "INSTALL_FAILED_UPDATE_INCOMPATIBLE" && non_interactive.no() =>
+            {
+                let answer = loop {
+                    if let Some(answer) = prompt::yes_no(
+                        "The installed app was signed with a different key. Would you like to uninstall it and try again? This will delete the app's data!",
+                        Some(prompt::YesOrNo::No),
+                    )
+                    .map_err(ApkInstallError::PromptFailed)?
+                    {
+                        break answer;
+                    }
+                };
+                if answer.yes() {
+                    self.uninstall(env, &package);
+                    self.try_install_apk(env, &apk_path)
+                } else {
+                    Err(ApkInstallError::InstallRejected {
+                        code,
+                        reason: "signatures do not match the previously installed version"
+                            .to_owned(),
+                    })
+                }
+            }
+            result => result,
+        }
     }
 
     fn wake_screen(&self, env: &Env) -> bossy::Result<()> {
@@ -187,66 +567,681 @@ impl<'a> Device<'a> {
         Ok(())
     }
 
+    fn launch_env(&self, env: &Env, package: &str) -> BTreeMap<String, String> {
+        match self
+            .adb(env)
+            .with_args(&["shell", "cat", &util::shell_quote(launch_env_path(package))])
+            .run_and_wait_for_output()
+        {
+            Ok(output) => String::from_utf8_lossy(output.stdout())
+                .lines()
+                .filter_map(|line| {
+                    let mut parts = line.splitn(2, '=');
+                    let key = parts.next()?;
+                    let value = parts.next()?;
+                    Some((key.to_owned(), value.to_owned()))
+                })
+                .collect(),
+            Err(err) => {
+                log::info!("no launch env found on device: {}", err);
+                Default::default()
+            }
+        }
+    }
+
+    // The whole file is replaced, so nothing from previous runs lingers.
+    fn set_launch_env(
+        &self,
+        env: &Env,
+        package: &str,
+        launch_env: &BTreeMap<String, String>,
+    ) -> Result<(), LaunchEnvError> {
+        let contents = launch_env
+            .iter()
+            .map(|(key, value)| {
+                if value.contains('\n') {
+                    log::warn!(
+                        "the value of launch env var {:?} contains a newline, so the app will only see the first line",
+                        key
+                    );
+                }
+                format!("{}={}\n", key, value)
+            })
+            .collect::<String>();
+        let path = util::temp_dir().join(LAUNCH_ENV_FILE_NAME);
+        fs::create_dir_all(util::temp_dir())
+            .and_then(|()| fs::write(&path, contents))
+            .map_err(|cause| LaunchEnvError::WriteFailed {
+                path: path.clone(),
+                cause,
+            })?;
+        let dest = launch_env_path(package);
+        self.adb(env)
+            .with_arg("push")
+            .with_arg(&path)
+            .with_arg(&dest)
+            .run_and_wait()
+            .map_err(LaunchEnvError::PushFailed)?;
+        self.adb(env)
+            .with_args(&["shell", "chmod", "a+r", &util::shell_quote(&dest)])
+            .run_and_wait()
+            .map_err(LaunchEnvError::ChmodFailed)?;
+        Ok(())
+    }
+
+    // Failing to uninstall is fine, since the app might not be installed yet.
+    fn uninstall(&self, env: &Env, package: &str) {
+        if let Err(err) = self
+            .adb(env)
+            .with_args(&["uninstall", package])
+            .run_and_wait_for_output()
+        {
+            log::info!("failed to uninstall {}: {}", package, err);
+        }
+    }
+
     pub fn run(
         &self,
         config: &Config,
+        metadata: &Metadata,
         env: &Env,
         noise_level: NoiseLevel,
+        non_interactive: opts::NonInteractive,
         profile: Profile,
+        options: &RunOptions,
     ) -> Result<(), RunError> {
-        self.build_apk(config, env, noise_level, profile)
-            .map_err(RunError::ApkBuildFailed)?;
-        self.install_apk(config, env, profile)
+        let package = config.package_name();
+        if !options.no_build {
+            self.build_apk(config, metadata, env, noise_level, profile)
+                .map_err(RunError::ApkBuildFailed)?;
+        }
+        if options.uninstall_first {
+            self.uninstall(env, &package);
+        }
+        self.install_apk(config, env, non_interactive, profile)
             .map_err(RunError::ApkInstallFailed)?;
-        let activity = format!(
-            "{}.{}/android.app.NativeActivity",
-            config.app().reverse_domain(),
-            config.app().name_snake(),
-        );
-        self.adb(env)
-            .with_args(&["shell", "am", "start", "-n", &activity])
+        for &port in &options.reverse {
+            let port_spec = format!("tcp:{}", port);
+            self.adb(env)
+                .with_args(&["reverse", &port_spec, &port_spec])
+                .run_and_wait()
+                .map_err(|cause| RunError::ReverseFailed { port, cause })?;
+        }
+        if options.clear_data {
+            self.adb(env)
+                .with_args(&["shell", "pm", "clear", &package])
+                .run_and_wait()
+                .map_err(RunError::ClearDataFailed)?;
+        }
+        if options.watch {
+            // Assets left over from an earlier session would otherwise shadow
+            // the ones in the APK we just installed.
+            self.sync_assets(config, env, false)
+                .map_err(RunError::AssetSyncFailed)?;
+            if !options.no_logcat {
+                self.follow_logs(config, env, noise_level);
+            }
+        }
+        self.start(config, env, options)?;
+        if options.no_logcat || options.watch {
+            return Ok(());
+        }
+        if options.stop_on_exit {
+            // Ctrl-C goes to `adb logcat` too, so we just need to outlive it.
+            if let Err(err) = ctrlc::set_handler(|| ()) {
+                log::warn!("failed to set Ctrl-C handler: {}", err);
+            }
+        }
+        let result = self
+            .logs(config, env, noise_level, &Default::default())
+            .map_err(RunError::LogcatFailed);
+        if options.stop_on_exit {
+            self.adb(env)
+                .with_args(&["shell", "am", "force-stop", &package])
+                .run_and_wait()
+                .map_err(RunError::StopFailed)?;
+        }
+        result
+    }
+
+    // Watch mode restarts the app over and over, so instead of sticking to one
+    // PID, this picks up each new process as it starts. It keeps going in the
+    // background for as long as we do.
+    fn follow_logs(&self, config: &Config, env: &Env, noise_level: NoiseLevel) {
+        let options = logcat::Options::default();
+        let filter = logcat::Filter {
+            package: Some(config.package_name()),
+            ..options.filter(None, noise_level)
+        };
+        // `-T 1` skips whatever's already in the buffer, which would include
+        // logs from earlier runs.
+        let command = self.adb(env).with_args(&["logcat", "-T", "1"]);
+        thread::spawn(move || {
+            if let Err(err) = logcat::stream(command, &filter, &options) {
+                log::warn!("stopped showing logs: {:?}", err);
+            }
+        });
+    }
+
+    fn start_command(&self, config: &Config, env: &Env, options: &RunOptions) -> bossy::Command {
+        let activity = format!("{}/{}", config.package_name(), config.activity().class());
+        let mut command = self.adb(env);
+        command.add_args(&["shell", "am", "start", "-n", &activity]);
+        if options.wait_for_debugger {
+            command.add_arg("-D");
+        }
+        // `adb shell` hands the whole command line to the device's shell, so
+        // anything with spaces in it would otherwise get split up.
+        for (key, value) in &options.launch_args {
+            command.add_arg("--es");
+            command.add_arg(util::shell_quote(key));
+            command.add_arg(util::shell_quote(value));
+        }
+        command
+    }
+
+    fn start(&self, config: &Config, env: &Env, options: &RunOptions) -> Result<(), RunError> {
+        let package = config.package_name();
+        let mut launch_env = config.launch_env().clone();
+        launch_env.extend(options.env.iter().cloned());
+        if options.watch {
+            launch_env.insert(ASSET_DIR_ENV.to_owned(), synced_asset_dir(&package));
+        }
+        self.set_launch_env(env, &package, &launch_env)
+            .map_err(RunError::LaunchEnvFailed)?;
+        self.start_command(config, env, options)
             .run_and_wait()
             .map_err(RunError::StartFailed)?;
         self.wake_screen(env).map_err(RunError::WakeScreenFailed)?;
-        let filter = format!(
-            "{}:{}",
-            config.app().name(),
-            match noise_level {
-                NoiseLevel::Polite => "W",
-                NoiseLevel::LoudAndProud => "I",
-                NoiseLevel::FranklyQuitePedantic => "V",
-            },
+        Ok(())
+    }
+
+    fn restart(&self, config: &Config, env: &Env, options: &RunOptions) -> Result<(), RunError> {
+        self.adb(env)
+            .with_args(&["shell", "am", "force-stop", &config.package_name()])
+            .run_and_wait()
+            .map_err(RunError::StopFailed)?;
+        self.start(config, env, options)
+    }
+
+    // Anything missing from the manifest on the device (including the manifest
+    // itself) just gets pushed.
+    fn synced_asset_hashes(&self, env: &Env, dir: &str) -> BTreeMap<String, String> {
+        let manifest = format!("{}/{}", dir, ASSET_MANIFEST_NAME);
+        match self
+            .adb(env)
+            .with_args(&["shell", "cat", &util::shell_quote(&manifest)])
+            .run_and_wait_for_output()
+        {
+            Ok(output) => String::from_utf8_lossy(output.stdout())
+                .lines()
+                .filter_map(|line| {
+                    let mut parts = line.splitn(2, "  ");
+                    let hash = parts.next()?;
+                    let path = parts.next()?;
+                    Some((path.to_owned(), hash.to_owned()))
+                })
+                .collect(),
+            Err(err) => {
+                log::info!("no asset manifest found on device: {}", err);
+                Default::default()
+            }
+        }
+    }
+
+    pub fn sync_assets(
+        &self,
+        config: &Config,
+        env: &Env,
+        restart: bool,
+    ) -> Result<(), AssetSyncError> {
+        let package = config.package_name();
+        let asset_dir = config.app().asset_dir();
+        let dest = synced_asset_dir(&package);
+        let mut local = BTreeMap::new();
+        hash_assets(&asset_dir, &asset_dir, &mut local)?;
+        let remote = self.synced_asset_hashes(env, &dest);
+
+        let removed = remote
+            .keys()
+            .filter(|path| !local.contains_key(*path))
+            .map(|path| util::shell_quote(format!("{}/{}", dest, path)))
+            .collect::<Vec<_>>();
+        if !removed.is_empty() {
+            println!("Removing {} deleted asset(s)...", removed.len());
+            self.adb(env)
+                .with_args(&["shell", "rm", "-f"])
+                .with_args(&removed)
+                .run_and_wait()
+                .map_err(AssetSyncError::RemoveFailed)?;
+        }
+
+        let changed = local
+            .iter()
+            .filter(|(path, hash)| remote.get(*path) != Some(hash))
+            .map(|(path, _)| path)
+            .collect::<Vec<_>>();
+        println!(
+            "Pushing {} changed asset(s) ({} unchanged)...",
+            changed.len(),
+            local.len() - changed.len()
         );
-        adb::adb(env, &self.serial_no)
-            .with_args(&["logcat", "-v", "color", "-s", &filter])
+        for path in changed {
+            let src = asset_dir.join(path);
+            self.adb(env)
+                .with_arg("push")
+                .with_arg(&src)
+                .with_arg(format!("{}/{}", dest, path))
+                .run_and_wait()
+                .map_err(|cause| AssetSyncError::PushFailed { path: src, cause })?;
+        }
+
+        let manifest = util::temp_dir().join(ASSET_MANIFEST_NAME);
+        let contents = local
+            .iter()
+            .map(|(path, hash)| format!("{}  {}\n", hash, path))
+            .collect::<String>();
+        fs::create_dir_all(util::temp_dir())
+            .and_then(|()| fs::write(&manifest, contents))
+            .map_err(|cause| AssetSyncError::ManifestWriteFailed {
+                path: manifest.clone(),
+                cause,
+            })?;
+        self.adb(env)
+            .with_arg("push")
+            .with_arg(&manifest)
+            .with_arg(format!("{}/{}", dest, ASSET_MANIFEST_NAME))
             .run_and_wait()
-            .map_err(RunError::LogcatFailed)?;
+            .map_err(|cause| AssetSyncError::PushFailed {
+                path: manifest,
+                cause,
+            })?;
+        // `adb push` runs as the shell user, so the app can't read anything
+        // unless we open up the permissions.
+        self.adb(env)
+            .with_args(&["shell", "chmod", "-R", "a+rX", &util::shell_quote(&dest)])
+            .run_and_wait()
+            .map_err(AssetSyncError::ChmodFailed)?;
+        // Only this one var is touched, so the rest of the launch env stays as
+        // `run` left it.
+        let mut launch_env = self.launch_env(env, &package);
+        launch_env.insert(ASSET_DIR_ENV.to_owned(), dest);
+        self.set_launch_env(env, &package, &launch_env)
+            .map_err(AssetSyncError::LaunchEnvFailed)?;
+
+        if restart {
+            println!("Restarting {}...", package);
+            self.adb(env)
+                .with_args(&["shell", "am", "force-stop", &package])
+                .run_and_wait()
+                .and_then(|_| {
+                    self.start_command(config, env, &Default::default())
+                        .run_and_wait()
+                })
+                .map_err(AssetSyncError::RestartFailed)?;
+        }
         Ok(())
     }
 
-    pub fn stacktrace(&self, config: &Config, env: &Env) -> Result<(), StacktraceError> {
-        // -d = print and exit
-        let logcat_command = adb::adb(env, &self.serial_no).with_args(&["logcat", "-d"]);
-        let stack_command = bossy::Command::pure("ndk-stack")
-            .with_env_vars(env.explicit_env())
-            .with_env_var(
-                "PATH",
-                util::prepend_to_path(env.ndk.home().display(), env.path()),
-            )
-            .with_arg("-sym")
-            .with_arg(
-                config
-                    .app()
-                    // ndk-stack can't seem to handle spaces in args, no matter
-                    // how I try to quote or escape them... so, instead of
-                    // mandating that the entire path not contain spaces, we'll
-                    // just use a relative path!
-                    .unprefix_path(self.target.get_jnilibs_subdir(config))
-                    .expect("developer error: jnilibs subdir not prefixed"),
-            );
-        if !util::pipe(logcat_command, stack_command).map_err(StacktraceError::PipeFailed)? {
-            println!("  -- no stacktrace --");
+    // Runs until killed; failures are reported, but don't stop the loop, since
+    // the next save will probably fix them. Nobody's around to answer prompts
+    // mid-loop, so installs are always non-interactive.
+    pub fn watch(
+        &self,
+        config: &Config,
+        metadata: &Metadata,
+        env: &Env,
+        noise_level: NoiseLevel,
+        profile: Profile,
+        options: &RunOptions,
+        wrapper: &TextWrapper,
+    ) {
+        let asset_dir = config.app().asset_dir();
+        let mut watcher = Watcher::new(config.app().watch_paths());
+        loop {
+            println!("Watching for changes; press Ctrl-C to stop...");
+            let changed = watcher.wait();
+            log::info!("changed: {:?}", changed);
+            let result = if watch::all_under(&changed, &asset_dir) {
+                println!("Assets changed; pushing and restarting...");
+                self.sync_assets(config, env, false)
+                    .map_err(RunError::AssetSyncFailed)
+                    .and_then(|()| self.restart(config, env, options))
+            } else {
+                println!("Sources changed; rebuilding...");
+                self.build_apk(config, metadata, env, noise_level, profile)
+                    .map_err(RunError::ApkBuildFailed)
+                    .and_then(|()| {
+                        self.install_apk(config, env, opts::NonInteractive::Yes, profile)
+                            .map_err(RunError::ApkInstallFailed)
+                    })
+                    .and_then(|()| self.restart(config, env, options))
+            };
+            if let Err(err) = result {
+                err.report().print(wrapper);
+            }
+        }
+    }
+
+    // The app can take a moment to actually get a process after `am start`
+    // returns, so we give it a few tries.
+    pub(super) fn pid(&self, env: &Env, package: &str) -> Option<u32> {
+        for attempt in 0..10 {
+            if attempt > 0 {
+                thread::sleep(Duration::from_millis(500));
+            }
+            match self
+                .adb(env)
+                .with_args(&["shell", "pidof", "-s", package])
+                .run_and_wait_for_output()
+            {
+                Ok(output) => {
+                    if let Ok(pid) = String::from_utf8_lossy(output.stdout()).trim().parse() {
+                        return Some(pid);
+                    }
+                }
+                // `pidof` exits with 1 if there's no matching process
+                Err(err) => log::info!("`pidof {}` failed: {}", package, err),
+            }
+        }
+        None
+    }
+
+    pub fn logs(
+        &self,
+        config: &Config,
+        env: &Env,
+        noise_level: NoiseLevel,
+        options: &logcat::Options,
+    ) -> Result<(), logcat::Error> {
+        let pid = if options.all_processes {
+            None
+        } else {
+            let package = config.package_name();
+            let pid = self.pid(env, &package);
+            if pid.is_none() {
+                log::warn!(
+                    "{} doesn't seem to be running; showing logs from all processes",
+                    package
+                );
+            }
+            pid
+        };
+        logcat::stream(
+            self.adb(env).with_arg("logcat"),
+            &options.filter(pid, noise_level),
+            options,
+        )
+    }
+
+    pub fn screenshot(
+        &self,
+        config: &Config,
+        env: &Env,
+        path: Option<PathBuf>,
+    ) -> Result<PathBuf, ToolError> {
+        let path = path.unwrap_or_else(|| capture_path(&config.package_name(), "png"));
+        // `exec-out` doesn't mangle line endings like `shell` does on older
+        // devices, which would corrupt the PNG.
+        let output = self
+            .adb(env)
+            .with_args(&["exec-out", "screencap", "-p"])
+            .run_and_wait_for_output()
+            .map_err(ToolError::ScreenshotFailed)?;
+        fs::write(&path, output.stdout()).map_err(|cause| ToolError::WriteFailed {
+            path: path.clone(),
+            cause,
+        })?;
+        Ok(path)
+    }
+
+    pub fn record(
+        &self,
+        config: &Config,
+        env: &Env,
+        path: Option<PathBuf>,
+        duration: u32,
+    ) -> Result<PathBuf, ToolError> {
+        static REMOTE_PATH: &str = "/sdcard/cargo-mobile-record.mp4";
+        let path = path.unwrap_or_else(|| capture_path(&config.package_name(), "mp4"));
+        println!("Recording for {} seconds...", duration);
+        self.adb(env)
+            .with_args(&["shell", "screenrecord", "--time-limit"])
+            .with_arg(duration.to_string())
+            .with_arg(REMOTE_PATH)
+            .run_and_wait()
+            .map_err(ToolError::RecordFailed)?;
+        self.adb(env)
+            .with_args(&["pull", REMOTE_PATH])
+            .with_arg(&path)
+            .run_and_wait()
+            .map_err(ToolError::RecordFailed)?;
+        if let Err(err) = self
+            .adb(env)
+            .with_args(&["shell", "rm", "-f", REMOTE_PATH])
+            .run_and_wait()
+        {
+            log::warn!("failed to delete recording from device: {}", err);
         }
+        Ok(path)
+    }
+
+    pub fn shell(&self, env: &Env, args: &[String]) -> Result<(), ToolError> {
+        self.adb(env)
+            .with_arg("shell")
+            .with_args(args)
+            .run_and_wait()
+            .map_err(ToolError::ShellFailed)?;
+        Ok(())
+    }
+
+    // The app's data dir isn't accessible to the shell user, so files are
+    // staged in `/data/local/tmp` and copied over by `run-as`, which starts
+    // out in the data dir; that's what makes `dest` relative to it.
+    pub fn push(
+        &self,
+        config: &Config,
+        env: &Env,
+        src: &Path,
+        dest: &str,
+    ) -> Result<(), ToolError> {
+        static STAGING_PATH: &str = "/data/local/tmp/cargo-mobile-push";
+        let map_err = |cause| ToolError::PushFailed {
+            path: src.to_owned(),
+            cause,
+        };
+        self.adb(env)
+            .with_arg("push")
+            .with_arg(src)
+            .with_arg(STAGING_PATH)
+            .run_and_wait()
+            .map_err(map_err)?;
+        let result = self
+            .adb(env)
+            .with_args(&[
+                "shell",
+                "run-as",
+                &config.package_name(),
+                "cp",
+                STAGING_PATH,
+            ])
+            .with_arg(util::shell_quote(dest))
+            .run_and_wait()
+            .map_err(map_err);
+        if let Err(err) = self
+            .adb(env)
+            .with_args(&["shell", "rm", "-f", STAGING_PATH])
+            .run_and_wait()
+        {
+            log::warn!("failed to delete staged file from device: {}", err);
+        }
+        result.map(|_| ())
+    }
+
+    pub fn pull(
+        &self,
+        config: &Config,
+        env: &Env,
+        src: &str,
+        dest: Option<PathBuf>,
+    ) -> Result<PathBuf, ToolError> {
+        let dest = dest.unwrap_or_else(|| {
+            Path::new(src)
+                .file_name()
+                .map(PathBuf::from)
+                .unwrap_or_else(|| PathBuf::from(src))
+        });
+        let output = self
+            .adb(env)
+            .with_args(&["exec-out", "run-as", &config.package_name(), "cat"])
+            .with_arg(util::shell_quote(src))
+            .run_and_wait_for_output()
+            .map_err(|cause| ToolError::PullFailed {
+                path: src.to_owned(),
+                cause,
+            })?;
+        fs::write(&dest, output.stdout()).map_err(|cause| ToolError::WriteFailed {
+            path: dest.clone(),
+            cause,
+        })?;
+        Ok(dest)
+    }
+
+    pub fn uninstall_app(&self, config: &Config, env: &Env) -> Result<(), ToolError> {
+        self.adb(env)
+            .with_args(&["uninstall", &config.package_name()])
+            .run_and_wait()
+            .map_err(ToolError::UninstallFailed)?;
+        Ok(())
+    }
+
+    pub fn clear_data(&self, config: &Config, env: &Env) -> Result<(), ToolError> {
+        self.adb(env)
+            .with_args(&["shell", "pm", "clear", &config.package_name()])
+            .run_and_wait()
+            .map_err(ToolError::ClearDataFailed)?;
         Ok(())
     }
+
+    fn latest_tombstone(
+        &self,
+        env: &Env,
+        bugreport: bool,
+    ) -> Result<Option<(String, String)>, StacktraceError> {
+        static TOMBSTONE_DIR: &str = "/data/tombstones";
+        // Tombstones are generally only readable on emulators and rooted
+        // devices, but it's worth a try since it's so much faster than the
+        // alternative.
+        match self
+            .adb(env)
+            .with_args(&["shell", "ls", "-t", TOMBSTONE_DIR])
+            .run_and_wait_for_output()
+        {
+            Ok(output) => {
+                let listing = String::from_utf8_lossy(output.stdout());
+                // An empty listing could just as well mean we weren't allowed
+                // to see anything, so that's no reason to give up yet.
+                if let Some(name) = listing
+                    .lines()
+                    .map(str::trim)
+                    .find(|name| name.starts_with("tombstone_") && !name.ends_with(".pb"))
+                {
+                    let path = format!("{}/{}", TOMBSTONE_DIR, name);
+                    return self
+                        .adb(env)
+                        .with_args(&["shell", "cat", &path])
+                        .run_and_wait_for_output()
+                        .map(|output| Some((path, String::from_utf8_lossy(output.stdout()).into())))
+                        .map_err(StacktraceError::TombstoneReadFailed);
+                }
+                log::info!("no readable tombstones found in {:?}", TOMBSTONE_DIR);
+            }
+            Err(err) => log::info!("failed to list tombstones directly: {}", err),
+        }
+        if bugreport {
+            self.tombstone_from_bugreport(env)
+        } else {
+            println!("Tombstones aren't directly readable on this device; pass `--bugreport` to pull them from a bugreport instead (this is slow!)");
+            Ok(None)
+        }
+    }
+
+    fn tombstone_from_bugreport(
+        &self,
+        env: &Env,
+    ) -> Result<Option<(String, String)>, StacktraceError> {
+        let dir = util::temp_dir();
+        fs::create_dir_all(&dir).map_err(|cause| StacktraceError::TempDirCreationFailed {
+            path: dir.clone(),
+            cause,
+        })?;
+        let zip_path = dir.join(format!("bugreport-{}.zip", self.serial_no));
+        println!("Generating bugreport; this can take a few minutes...");
+        self.adb(env)
+            .with_arg("bugreport")
+            .with_arg(&zip_path)
+            .run_and_wait()
+            .map_err(StacktraceError::BugreportFailed)?;
+        // `-Z -T` gives us sortable timestamps as the second-to-last column
+        let listing = bossy::Command::pure("unzip")
+            .with_env_vars(env.explicit_env())
+            .with_args(&["-Z", "-T"])
+            .with_arg(&zip_path)
+            .run_and_wait_for_output()
+            .map_err(StacktraceError::UnzipFailed)?;
+        let listing = String::from_utf8_lossy(listing.stdout());
+        let latest = listing
+            .lines()
+            .filter_map(|line| {
+                let mut columns = line.split_whitespace().rev();
+                let name = columns.next()?;
+                let timestamp = columns.next()?;
+                if name.contains("tombstones/tombstone_") && !name.ends_with(".pb") {
+                    Some((timestamp, name))
+                } else {
+                    None
+                }
+            })
+            .max();
+        latest
+            .map(|(_, name)| {
+                bossy::Command::pure("unzip")
+                    .with_env_vars(env.explicit_env())
+                    .with_arg("-p")
+                    .with_arg(&zip_path)
+                    .with_arg(name)
+                    .run_and_wait_for_output()
+                    .map(|output| {
+                        (
+                            format!("{}:{}", zip_path.display(), name),
+                            String::from_utf8_lossy(output.stdout()).into(),
+                        )
+                    })
+                    .map_err(StacktraceError::UnzipFailed)
+            })
+            .transpose()
+    }
+
+    pub fn stacktrace(
+        &self,
+        config: &Config,
+        env: &Env,
+        profile: Profile,
+        bugreport: bool,
+        output_format: OutputFormat,
+    ) -> Result<(), StacktraceError> {
+        // -d = print and exit
+        let logcat = self
+            .adb(env)
+            .with_args(&["logcat", "-d"])
+            .run_and_wait_for_output()
+            .map_err(StacktraceError::LogcatFailed)?;
+        let mut crashes = Crash::parse("logcat", &String::from_utf8_lossy(logcat.stdout()))
+            .into_iter()
+            .collect::<Vec<_>>();
+        if let Some((path, raw)) = self.latest_tombstone(env, bugreport)? {
+            crashes.extend(Crash::parse(path, &raw));
+        }
+        stacktrace::report(config, env, profile, self.target, crashes, output_format)
+    }
 }
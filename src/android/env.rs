@@ -5,7 +5,7 @@ use crate::{
 };
 use std::{
     fmt::{self, Display},
-    path::PathBuf,
+    path::{Path, PathBuf},
 };
 
 #[derive(Debug)]
@@ -103,6 +103,10 @@ impl Env {
     pub fn path(&self) -> &str {
         self.base.path()
     }
+
+    pub fn sdk_root(&self) -> &Path {
+        &self.sdk_root
+    }
 }
 
 impl ExplicitEnv for Env {
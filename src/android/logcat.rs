@@ -0,0 +1,544 @@
+use crate::{
+    opts::NoiseLevel,
+    util::cli::{self, Report, Reportable},
+};
+use colored::{Color, Colorize as _};
+use once_cell_regex::{exports::regex::Regex, regex};
+use serde::Serialize;
+use std::{
+    fmt::{self, Display},
+    fs::File,
+    io::{self, BufRead as _, BufReader, Write as _},
+    path::PathBuf,
+    str::FromStr,
+};
+use structopt::StructOpt;
+
+// Crash dumps are written by `crash_dump`/`debuggerd` rather than the app
+// itself, so we'd never see them if we only looked at the app's PID.
+static CRASH_TAGS: &[&str] = &["DEBUG", "AndroidRuntime", "libc"];
+
+#[derive(Debug)]
+pub enum Error {
+    LogcatFailed(bossy::Error),
+    ReadFailed(io::Error),
+    TeeCreationFailed { path: PathBuf, cause: io::Error },
+    TeeWriteFailed { path: PathBuf, cause: io::Error },
+    SerializeFailed(serde_json::Error),
+}
+
+impl Reportable for Error {
+    fn report(&self) -> Report {
+        match self {
+            Self::LogcatFailed(err) => Report::error("Failed to run `adb logcat`", err),
+            Self::ReadFailed(err) => Report::error("Failed to read `adb logcat` output", err),
+            Self::TeeCreationFailed { path, cause } => {
+                Report::error(format!("Failed to create log file {:?}", path), cause)
+            }
+            Self::TeeWriteFailed { path, cause } => {
+                Report::error(format!("Failed to write to log file {:?}", path), cause)
+            }
+            Self::SerializeFailed(err) => Report::error("Failed to serialize log record", err),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+pub enum Level {
+    Verbose,
+    Debug,
+    Info,
+    Warn,
+    Error,
+    Fatal,
+    Silent,
+}
+
+impl Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_char())
+    }
+}
+
+impl FromStr for Level {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Self::from_char(c.to_ascii_uppercase()),
+            _ => match s.to_ascii_lowercase().as_str() {
+                "verbose" => Some(Self::Verbose),
+                "debug" => Some(Self::Debug),
+                "info" => Some(Self::Info),
+                "warn" | "warning" => Some(Self::Warn),
+                "error" => Some(Self::Error),
+                "fatal" => Some(Self::Fatal),
+                "silent" => Some(Self::Silent),
+                _ => None,
+            },
+        }
+        .ok_or_else(|| format!("{:?} isn't a valid log level", s))
+    }
+}
+
+impl Level {
+    pub fn from_noise_level(noise_level: NoiseLevel) -> Self {
+        match noise_level {
+            NoiseLevel::Polite => Self::Warn,
+            NoiseLevel::LoudAndProud => Self::Info,
+            NoiseLevel::FranklyQuitePedantic => Self::Verbose,
+        }
+    }
+
+    pub fn from_char(c: char) -> Option<Self> {
+        match c {
+            'V' => Some(Self::Verbose),
+            'D' => Some(Self::Debug),
+            'I' => Some(Self::Info),
+            'W' => Some(Self::Warn),
+            'E' => Some(Self::Error),
+            'F' => Some(Self::Fatal),
+            'S' => Some(Self::Silent),
+            _ => None,
+        }
+    }
+
+    pub fn as_char(self) -> char {
+        match self {
+            Self::Verbose => 'V',
+            Self::Debug => 'D',
+            Self::Info => 'I',
+            Self::Warn => 'W',
+            Self::Error => 'E',
+            Self::Fatal => 'F',
+            Self::Silent => 'S',
+        }
+    }
+
+    fn color(self) -> Option<Color> {
+        match self {
+            Self::Verbose | Self::Debug => Some(Color::BrightBlack),
+            Self::Info => None,
+            Self::Warn => Some(Color::Yellow),
+            Self::Error | Self::Fatal => Some(Color::BrightRed),
+            Self::Silent => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct Record {
+    pub date: String,
+    pub time: String,
+    pub pid: u32,
+    pub tid: u32,
+    pub level: Level,
+    pub tag: String,
+    pub message: String,
+}
+
+impl Display for Record {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {:>5} {:>5} {} {}: {}",
+            self.date, self.time, self.pid, self.tid, self.level, self.tag, self.message
+        )
+    }
+}
+
+impl Record {
+    // Parses a single line of `adb logcat -v threadtime` output, i.e.
+    // `10-18 12:34:56.789  1234  1250 I MyTag   : hello`. Lines that don't
+    // match (like the `--------- beginning of main` dividers) give `None`.
+    // The tag ends at the first `: `, since Rust module paths make for tags
+    // like `wgpu_core::instance`.
+    pub fn parse(line: &str) -> Option<Self> {
+        let caps = regex!(
+            r"^(?P<date>\d\d-\d\d)\s+(?P<time>\d\d:\d\d:\d\d\.\d+)\s+(?P<pid>\d+)\s+(?P<tid>\d+)\s+(?P<level>[VDIWEFS])\s+(?P<tag>.*?)\s*:(?: (?P<message>.*))?$"
+        )
+        .captures(line.trim_end())?;
+        Some(Self {
+            date: caps["date"].to_owned(),
+            time: caps["time"].to_owned(),
+            pid: caps["pid"].parse().ok()?,
+            tid: caps["tid"].parse().ok()?,
+            level: Level::from_char(caps["level"].chars().next()?)?,
+            tag: caps["tag"].to_owned(),
+            message: caps
+                .name("message")
+                .map_or_else(String::new, |message| message.as_str().to_owned()),
+        })
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Filter {
+    pub pid: Option<u32>,
+    // When set, `pid` follows this package across restarts, going by the
+    // `ActivityManager` logs that announce each new process. Nothing but
+    // crashes gets through until the first one shows up.
+    pub package: Option<String>,
+    pub min_level: Option<Level>,
+    pub tags: Vec<String>,
+    pub pattern: Option<Regex>,
+}
+
+impl Filter {
+    pub fn follow(&mut self, record: &Record) {
+        if let Some(package) = &self.package {
+            if record.tag == "ActivityManager" {
+                if let Some(caps) = regex!(r"^Start proc (\d+):([^/\s]+)").captures(&record.message)
+                {
+                    if &caps[2] == package.as_str() {
+                        self.pid = caps[1].parse().ok();
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn matches(&self, record: &Record) -> bool {
+        let is_crash = CRASH_TAGS.contains(&record.tag.as_str());
+        let pid_matches = match self.pid {
+            Some(pid) => record.pid == pid || is_crash,
+            None => self.package.is_none() || is_crash,
+        };
+        let level_matches = self
+            .min_level
+            .map_or(true, |min_level| record.level >= min_level);
+        let tag_matches = self.tags.is_empty() || self.tags.contains(&record.tag);
+        let pattern_matches = self.pattern.as_ref().map_or(true, |pattern| {
+            pattern.is_match(&record.message) || pattern.is_match(&record.tag)
+        });
+        pid_matches && level_matches && tag_matches && pattern_matches
+    }
+}
+
+#[derive(Clone, Debug, Default, StructOpt)]
+pub struct Options {
+    #[structopt(
+        long = "level",
+        help = "Minimum log level to show (V, D, I, W, E, or F)"
+    )]
+    pub level: Option<Level>,
+    #[structopt(
+        long = "tag",
+        help = "Only show logs with this tag",
+        number_of_values = 1
+    )]
+    pub tags: Vec<String>,
+    #[structopt(
+        long = "grep",
+        help = "Only show logs with a tag or message matching this regex",
+        parse(try_from_str = Regex::new)
+    )]
+    pub pattern: Option<Regex>,
+    #[structopt(
+        long = "all-processes",
+        help = "Show logs from all processes instead of just the app"
+    )]
+    pub all_processes: bool,
+    #[structopt(
+        long = "tee",
+        help = "Also write logs to this file",
+        parse(from_os_str)
+    )]
+    pub tee: Option<PathBuf>,
+    #[structopt(
+        long = "dump",
+        help = "Print existing logs and exit instead of following"
+    )]
+    pub dump: bool,
+    #[structopt(flatten)]
+    pub output_format: cli::OutputFormat,
+}
+
+impl Options {
+    pub fn filter(&self, pid: Option<u32>, noise_level: NoiseLevel) -> Filter {
+        Filter {
+            pid,
+            package: None,
+            min_level: Some(
+                self.level
+                    .unwrap_or_else(|| Level::from_noise_level(noise_level)),
+            ),
+            tags: self.tags.clone(),
+            pattern: self.pattern.clone(),
+        }
+    }
+}
+
+pub fn stream(
+    mut logcat_command: bossy::Command,
+    filter: &Filter,
+    options: &Options,
+) -> Result<(), Error> {
+    let output_format = options.output_format.output_format;
+    let mut filter = filter.clone();
+    let mut tee = options
+        .tee
+        .as_ref()
+        .map(|path| {
+            File::create(path)
+                .map(|file| (path, file))
+                .map_err(|cause| Error::TeeCreationFailed {
+                    path: path.clone(),
+                    cause,
+                })
+        })
+        .transpose()?;
+    logcat_command.add_args(&["-v", "threadtime"]);
+    if options.dump {
+        logcat_command.add_arg("-d");
+    }
+    let mut handle = logcat_command
+        .with_stdout_piped()
+        .run()
+        .map_err(Error::LogcatFailed)?;
+    let stdout = BufReader::new(
+        handle
+            .stdout()
+            .expect("developer error: `adb logcat` stdout not captured"),
+    );
+    for line in stdout.split(b'\n') {
+        let line = line.map_err(Error::ReadFailed)?;
+        let line = String::from_utf8_lossy(&line);
+        if let Some(record) = Record::parse(&line).filter(|record| {
+            filter.follow(record);
+            filter.matches(record)
+        }) {
+            let formatted = if output_format.json() {
+                serde_json::to_string(&record).map_err(Error::SerializeFailed)?
+            } else {
+                record.to_string()
+            };
+            match record.level.color() {
+                Some(color) if !output_format.json() => println!("{}", formatted.color(color)),
+                _ => println!("{}", formatted),
+            }
+            if let Some((path, file)) = &mut tee {
+                writeln!(file, "{}", formatted).map_err(|cause| Error::TeeWriteFailed {
+                    path: path.to_path_buf(),
+                    cause,
+                })?;
+            }
+        }
+    }
+    match handle.wait() {
+        // Being killed by a signal means the user hit Ctrl-C, which is the
+        // only way to stop following logs.
+        Err(err) if err.status().map_or(false, |status| status.code().is_none()) => Ok(()),
+        result => result.map(|_| ()).map_err(Error::LogcatFailed),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Captured from `adb logcat -v threadtime` on an emulator running the
+    // wgpu template, plus the usual dividers.
+    static FIXTURE: &str = "--------- beginning of main
+10-18 12:34:56.789  1234  1250 I RustStdoutStderr: hello from rust
+10-18 12:34:56.790  1234  1250 W wgpu_core::instance: Adapter: some \"quoted\" thing: here
+10-18 12:34:56.791  1234  1234 D MyTag   : padded tag
+10-18 12:34:56.792   987   990 E AndroidRuntime: FATAL EXCEPTION: main
+10-18 12:34:56.792   987   990 E AndroidRuntime: Process: com.example.other, PID: 987
+10-18 12:34:56.793  1234  1250 V Empty   : 
+--------- beginning of crash
+10-18 12:34:56.800   555   555 F DEBUG   : *** *** *** *** *** *** *** *** *** *** *** *** *** *** *** ***
+10-18 12:34:56.801  1234  1250 I RustStdoutStderr: windows line ending\r
+";
+
+    fn records() -> Vec<Record> {
+        FIXTURE.lines().filter_map(Record::parse).collect()
+    }
+
+    fn record(pid: u32, level: Level, tag: &str, message: &str) -> Record {
+        Record {
+            date: "10-18".to_owned(),
+            time: "12:34:56.789".to_owned(),
+            pid,
+            tid: pid,
+            level,
+            tag: tag.to_owned(),
+            message: message.to_owned(),
+        }
+    }
+
+    #[test]
+    fn parses_threadtime_line() {
+        assert_eq!(
+            Record::parse("10-18 12:34:56.789  1234  1250 I RustStdoutStderr: hello from rust"),
+            Some(Record {
+                date: "10-18".to_owned(),
+                time: "12:34:56.789".to_owned(),
+                pid: 1234,
+                tid: 1250,
+                level: Level::Info,
+                tag: "RustStdoutStderr".to_owned(),
+                message: "hello from rust".to_owned(),
+            })
+        );
+    }
+
+    #[test]
+    fn parses_fixture() {
+        let records = records();
+        assert_eq!(records.len(), 8);
+        assert_eq!(records[1].tag, "wgpu_core::instance");
+        assert_eq!(records[1].message, "Adapter: some \"quoted\" thing: here");
+        assert_eq!(records[2].tag, "MyTag");
+        assert_eq!(records[2].message, "padded tag");
+        assert_eq!(records[5].tag, "Empty");
+        assert_eq!(records[5].message, "");
+        assert_eq!(records[6].level, Level::Fatal);
+        assert_eq!(records[7].message, "windows line ending");
+    }
+
+    #[test]
+    fn trims_trailing_whitespace() {
+        let record = Record::parse("10-18 12:34:56.789  1234  1250 I Tag: message \r").unwrap();
+        assert_eq!(record.message, "message");
+    }
+
+    // Multi-line messages are split by logcat, with the header repeated on
+    // each line.
+    #[test]
+    fn parses_multi_line_message_as_separate_records() {
+        let records = records();
+        assert_eq!(records[3].message, "FATAL EXCEPTION: main");
+        assert_eq!(records[4].message, "Process: com.example.other, PID: 987");
+        assert_eq!((records[3].pid, records[3].tid), (987, 990));
+        assert_eq!((records[4].pid, records[4].tid), (987, 990));
+    }
+
+    #[test]
+    fn skips_malformed_lines() {
+        for line in &[
+            "",
+            "--------- beginning of system",
+            // Continuation lines without a header, like from `logcat -v raw`
+            "\tat com.example.MainActivity.onCreate(MainActivity.java:12)",
+            "10-18 12:34:56.789  1234",
+            "10-18 12:34:56.789  1234  1250 X BadLevel: nope",
+            "10-18 12:34:56.789  pid  1250 I BadPid: nope",
+            "10-18 12:34:56.789  1234  1250 I NoSeparator",
+            "99999999999 12:34:56.789  1234  1250 I BadDate: nope",
+        ] {
+            assert_eq!(Record::parse(line), None, "{:?}", line);
+        }
+    }
+
+    #[test]
+    fn default_filter_matches_everything() {
+        let filter = Filter::default();
+        assert!(records().iter().all(|record| filter.matches(record)));
+    }
+
+    #[test]
+    fn filters_by_pid_but_keeps_crashes() {
+        let filter = Filter {
+            pid: Some(1234),
+            ..Default::default()
+        };
+        assert!(filter.matches(&record(1234, Level::Info, "Mine", "hi")));
+        assert!(!filter.matches(&record(4321, Level::Info, "Theirs", "hi")));
+        assert!(filter.matches(&record(555, Level::Fatal, "DEBUG", "backtrace:")));
+        assert!(filter.matches(&record(987, Level::Error, "AndroidRuntime", "FATAL")));
+    }
+
+    #[test]
+    fn follows_package_across_restarts() {
+        let mut filter = Filter {
+            package: Some("com.example.app".to_owned()),
+            ..Default::default()
+        };
+        assert!(!filter.matches(&record(1234, Level::Info, "Mine", "too early")));
+        assert!(filter.matches(&record(555, Level::Fatal, "DEBUG", "backtrace:")));
+        for (line, pid) in &[
+            ("10-18 12:34:56.789   600   620 I ActivityManager: Start proc 4321:com.example.other/u0a86 for activity {com.example.other/android.app.NativeActivity}", None),
+            ("10-18 12:34:56.789   600   620 I ActivityManager: Start proc 1234:com.example.app/u0a85 for activity {com.example.app/android.app.NativeActivity}", Some(1234)),
+            ("10-18 12:34:57.789   600   620 I ActivityManager: Start proc 1300:com.example.app/u0a85 for pre-top-activity {com.example.app/android.app.NativeActivity}", Some(1300)),
+        ] {
+            filter.follow(&Record::parse(line).unwrap());
+            assert_eq!(filter.pid, *pid, "{:?}", line);
+        }
+        assert!(filter.matches(&record(1300, Level::Info, "Mine", "restarted")));
+        assert!(!filter.matches(&record(1234, Level::Info, "Mine", "old process")));
+        assert!(!filter.matches(&record(4321, Level::Info, "Theirs", "hi")));
+    }
+
+    #[test]
+    fn filters_by_level() {
+        let filter = Filter {
+            min_level: Some(Level::Warn),
+            ..Default::default()
+        };
+        assert!(!filter.matches(&record(1, Level::Verbose, "Tag", "")));
+        assert!(!filter.matches(&record(1, Level::Info, "Tag", "")));
+        assert!(filter.matches(&record(1, Level::Warn, "Tag", "")));
+        assert!(filter.matches(&record(1, Level::Fatal, "Tag", "")));
+    }
+
+    #[test]
+    fn filters_by_tag() {
+        let filter = Filter {
+            tags: vec!["RustStdoutStderr".to_owned(), "MyTag".to_owned()],
+            ..Default::default()
+        };
+        let tags = records()
+            .into_iter()
+            .filter(|record| filter.matches(record))
+            .map(|record| record.tag)
+            .collect::<Vec<_>>();
+        assert_eq!(tags, &["RustStdoutStderr", "MyTag", "RustStdoutStderr"]);
+    }
+
+    #[test]
+    fn filters_by_pattern_on_tag_or_message() {
+        let filter = Filter {
+            pattern: Some(Regex::new("^wgpu|line ending$").unwrap()),
+            ..Default::default()
+        };
+        let messages = records()
+            .into_iter()
+            .filter(|record| filter.matches(record))
+            .map(|record| record.message)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            &[
+                "Adapter: some \"quoted\" thing: here",
+                "windows line ending"
+            ]
+        );
+    }
+
+    #[test]
+    fn combines_filters() {
+        let filter = Filter {
+            pid: Some(1234),
+            package: None,
+            min_level: Some(Level::Info),
+            tags: vec!["RustStdoutStderr".to_owned()],
+            pattern: Some(Regex::new("hello").unwrap()),
+        };
+        let matched = records()
+            .into_iter()
+            .filter(|record| filter.matches(record))
+            .collect::<Vec<_>>();
+        assert_eq!(matched.len(), 1);
+        assert_eq!(matched[0].message, "hello from rust");
+    }
+
+    #[test]
+    fn parses_levels() {
+        assert_eq!("w".parse(), Ok(Level::Warn));
+        assert_eq!("Warning".parse(), Ok(Level::Warn));
+        assert_eq!("F".parse(), Ok(Level::Fatal));
+        assert!("loud".parse::<Level>().is_err());
+    }
+}
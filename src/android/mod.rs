@@ -1,10 +1,17 @@
+mod aar;
 mod adb;
+mod apk;
 pub mod cli;
 pub(crate) mod config;
+mod debug;
 mod device;
 pub(crate) mod env;
+mod logcat;
 mod ndk;
+mod profile;
 pub(crate) mod project;
+mod size;
+mod stacktrace;
 mod target;
 
 pub static NAME: &str = "android";
@@ -1,7 +1,7 @@
 use crate::util::cli::{Report, Reportable};
 use std::{
     fmt::{self, Display},
-    fs::File,
+    fs::{self, File},
     io,
     num::ParseIntError,
     path::{Path, PathBuf},
@@ -63,6 +63,33 @@ impl Binutil {
     }
 }
 
+#[derive(Clone, Copy, Debug)]
+pub enum LlvmTool {
+    Lldb,
+    Nm,
+    Objcopy,
+    Readelf,
+    Size,
+    Strip,
+    Symbolizer,
+}
+
+impl LlvmTool {
+    fn as_str(&self) -> &'static str {
+        match self {
+            // This wrapper sets up the Python environment LLDB's `script`
+            // command needs, which the bare `lldb` binary won't find.
+            LlvmTool::Lldb => "lldb.sh",
+            LlvmTool::Nm => "llvm-nm",
+            LlvmTool::Objcopy => "llvm-objcopy",
+            LlvmTool::Readelf => "llvm-readelf",
+            LlvmTool::Size => "llvm-size",
+            LlvmTool::Strip => "llvm-strip",
+            LlvmTool::Symbolizer => "llvm-symbolizer",
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct MissingToolError {
     name: &'static str,
@@ -286,6 +313,32 @@ impl Env {
         }
     }
 
+    pub fn sysroot(&self) -> Result<PathBuf, MissingToolError> {
+        let path = self
+            .ndk_home
+            .join(format!("toolchains/llvm/prebuilt/{}/sysroot", host_tag()));
+        if path.is_dir() {
+            Ok(path)
+        } else {
+            Err(MissingToolError {
+                name: "sysroot",
+                tried_path: path,
+            })
+        }
+    }
+
+    pub fn cmake_toolchain_path(&self) -> Result<PathBuf, MissingToolError> {
+        let path = self.ndk_home.join("build/cmake/android.toolchain.cmake");
+        if path.is_file() {
+            Ok(path)
+        } else {
+            Err(MissingToolError {
+                name: "android.toolchain.cmake",
+                tried_path: path,
+            })
+        }
+    }
+
     pub fn compiler_path(
         &self,
         compiler: Compiler,
@@ -322,4 +375,80 @@ impl Env {
             })
         }
     }
+
+    pub fn llvm_tool_path(&self, tool: LlvmTool) -> Result<PathBuf, MissingToolError> {
+        let path = self.tool_dir()?.join(tool.as_str());
+        if path.is_file() {
+            Ok(path)
+        } else {
+            Err(MissingToolError {
+                name: tool.as_str(),
+                tried_path: path,
+            })
+        }
+    }
+
+    // `lldb-server` lives next to the clang runtime libs, so its path depends
+    // on the clang version (and whether the NDK calls it `lib` or `lib64`).
+    pub fn lldb_server_path(&self, arch: &str) -> Result<PathBuf, MissingToolError> {
+        let prebuilt = self
+            .ndk_home
+            .join(format!("toolchains/llvm/prebuilt/{}", host_tag()));
+        let found = ["lib64/clang", "lib/clang"]
+            .iter()
+            .filter_map(|dir| fs::read_dir(prebuilt.join(dir)).ok())
+            .flatten()
+            .filter_map(Result::ok)
+            .map(|entry| entry.path().join(format!("lib/linux/{}/lldb-server", arch)))
+            .find(|path| path.is_file());
+        found.ok_or_else(|| MissingToolError {
+            name: "lldb-server",
+            tried_path: prebuilt.join(format!("lib64/clang/*/lib/linux/{}/lldb-server", arch)),
+        })
+    }
+
+    // Prebuilt binaries for both the device and the host live alongside the
+    // Python scripts that drive them.
+    pub fn simpleperf_dir(&self) -> Result<PathBuf, MissingToolError> {
+        let path = self.ndk_home.join("simpleperf");
+        if path.is_dir() {
+            Ok(path)
+        } else {
+            Err(MissingToolError {
+                name: "simpleperf",
+                tried_path: path,
+            })
+        }
+    }
+
+    pub fn simpleperf_path(&self, arch: &str) -> Result<PathBuf, MissingToolError> {
+        let path = self
+            .simpleperf_dir()?
+            .join(format!("bin/android/{}/simpleperf", arch));
+        if path.is_file() {
+            Ok(path)
+        } else {
+            Err(MissingToolError {
+                name: "simpleperf",
+                tried_path: path,
+            })
+        }
+    }
+
+    pub fn host_simpleperf_path(&self) -> Result<PathBuf, MissingToolError> {
+        let mut parts = host_tag().splitn(2, '-');
+        let os = parts.next().expect("developer error: host tag was empty");
+        let arch = parts.next().unwrap_or("x86");
+        let path = self
+            .simpleperf_dir()?
+            .join(format!("bin/{}/{}/simpleperf", os, arch));
+        if path.is_file() {
+            Ok(path)
+        } else {
+            Err(MissingToolError {
+                name: "simpleperf",
+                tried_path: path,
+            })
+        }
+    }
 }
@@ -0,0 +1,300 @@
+use super::{config::Config, device::Device, env::Env, ndk};
+use crate::{
+    env::ExplicitEnv as _,
+    opts::Profile,
+    util::{
+        self,
+        cli::{Report, Reportable},
+    },
+};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+use structopt::StructOpt;
+
+static DEVICE_SIMPLEPERF_PATH: &str = "/data/local/tmp/simpleperf";
+static DEVICE_DATA_PATH: &str = "/data/local/tmp/perf.data";
+static DATA_FILE_NAME: &str = "perf.data";
+static PERF_SCRIPT_FILE_NAME: &str = "perf.script";
+static FOLDED_FILE_NAME: &str = "perf.folded";
+
+#[derive(Debug)]
+pub enum Error {
+    NotRunning { package: String },
+    MissingTool(ndk::MissingToolError),
+    PushFailed(bossy::Error),
+    RecordFailed(bossy::Error),
+    DirCreationFailed { path: PathBuf, cause: io::Error },
+    PullFailed(bossy::Error),
+    BinaryCacheFailed(bossy::Error),
+    ReportFailed(bossy::Error),
+    FlamegraphFailed(bossy::Error),
+    WriteFailed { path: PathBuf, cause: io::Error },
+}
+
+impl Reportable for Error {
+    fn report(&self) -> Report {
+        match self {
+            Self::NotRunning { package } => Report::action_request(
+                format!("{} isn't running", package),
+                "Start it with `cargo android run`, then try again.",
+            ),
+            Self::MissingTool(err) => Report::error("Failed to locate `simpleperf` in NDK", err),
+            Self::PushFailed(err) => Report::error("Failed to push `simpleperf` to device", err),
+            Self::RecordFailed(err) => Report::action_request(
+                "Failed to record profile",
+                format!("`simpleperf` can only profile apps that are debuggable or profileable, so make sure a debug build is installed or that your manifest has `<profileable android:shell=\"true\" />`: {}", err),
+            ),
+            Self::DirCreationFailed { path, cause } => Report::error(
+                format!("Failed to create profile output directory {:?}", path),
+                cause,
+            ),
+            Self::PullFailed(err) => Report::error("Failed to pull profile from device", err),
+            Self::BinaryCacheFailed(err) => Report::action_request(
+                "Failed to collect binaries for symbolization",
+                format!("`simpleperf`'s scripts need Python 3, so make sure `python3` is installed: {}", err),
+            ),
+            Self::ReportFailed(err) => Report::error("Failed to generate profile report", err),
+            Self::FlamegraphFailed(err) => Report::action_request(
+                "Failed to generate flamegraph",
+                format!("Flamegraphs are rendered with inferno, so make sure it's installed (`cargo install inferno`): {}", err),
+            ),
+            Self::WriteFailed { path, cause } => {
+                Report::error(format!("Failed to write profile report to {:?}", path), cause)
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Format {
+    Report,
+    Html,
+    PerfScript,
+    Flamegraph,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "report" => Ok(Self::Report),
+            "html" => Ok(Self::Html),
+            "perf-script" => Ok(Self::PerfScript),
+            "flamegraph" => Ok(Self::Flamegraph),
+            _ => Err(format!(
+                "{:?} isn't a valid format; expected one of: report, html, perf-script, flamegraph",
+                s
+            )),
+        }
+    }
+}
+
+impl Format {
+    fn file_name(self) -> &'static str {
+        match self {
+            Self::Report => "report.txt",
+            Self::Html => "report.html",
+            Self::PerfScript => PERF_SCRIPT_FILE_NAME,
+            Self::Flamegraph => "flamegraph.svg",
+        }
+    }
+}
+
+#[derive(Clone, Debug, StructOpt)]
+pub struct Options {
+    #[structopt(
+        long = "duration",
+        help = "How many seconds to record for",
+        default_value = "10"
+    )]
+    pub duration: u32,
+    #[structopt(
+        long = "format",
+        help = "What to generate from the recording (report, html, perf-script, or flamegraph)",
+        default_value = "report"
+    )]
+    pub format: Format,
+    #[structopt(
+        long = "out-dir",
+        help = "Where to put the recording and report, instead of `target/simpleperf`",
+        parse(from_os_str)
+    )]
+    pub out_dir: Option<PathBuf>,
+}
+
+// simpleperf's scripts look for `binary_cache` in the current dir, so
+// everything involving them has to run from the output dir. Some of them pull
+// from the device too, so they need to know which one.
+fn script(device: &Device<'_>, env: &Env, dir: &Path, command: String) -> bossy::Command {
+    bossy::Command::pure("sh")
+        .with_env_vars(env.explicit_env())
+        .with_env_var("ANDROID_SERIAL", device.serial_no())
+        .with_arg("-c")
+        .with_arg(format!("cd {} && {}", util::shell_quote(dir), command))
+}
+
+fn record(device: &Device<'_>, config: &Config, env: &Env, options: &Options) -> Result<(), Error> {
+    let package = config.package_name();
+    if device.pid(env, &package).is_none() {
+        return Err(Error::NotRunning { package });
+    }
+    let simpleperf = env
+        .ndk
+        .simpleperf_path(device.target().arch)
+        .map_err(Error::MissingTool)?;
+    device
+        .adb(env)
+        .with_arg("push")
+        .with_arg(&simpleperf)
+        .with_arg(DEVICE_SIMPLEPERF_PATH)
+        .run_and_wait()
+        .and_then(|_| {
+            device
+                .adb(env)
+                .with_args(&["shell", "chmod", "755", DEVICE_SIMPLEPERF_PATH])
+                .run_and_wait()
+        })
+        .map_err(Error::PushFailed)?;
+    println!("Profiling {} for {} seconds...", package, options.duration);
+    device
+        .adb(env)
+        .with_args(&[
+            "shell",
+            DEVICE_SIMPLEPERF_PATH,
+            "record",
+            "--app",
+            &package,
+            "--duration",
+            &options.duration.to_string(),
+            "-g",
+            "-o",
+            DEVICE_DATA_PATH,
+        ])
+        .run_and_wait()
+        .map_err(Error::RecordFailed)?;
+    Ok(())
+}
+
+// Returns the path to whatever was generated.
+pub fn run(
+    device: &Device<'_>,
+    config: &Config,
+    env: &Env,
+    profile: Profile,
+    options: &Options,
+) -> Result<PathBuf, Error> {
+    let simpleperf_dir = env.ndk.simpleperf_dir().map_err(Error::MissingTool)?;
+    let out_dir = options
+        .out_dir
+        .clone()
+        .unwrap_or_else(|| config.app().prefix_path("target/simpleperf"));
+    fs::create_dir_all(&out_dir).map_err(|cause| Error::DirCreationFailed {
+        path: out_dir.clone(),
+        cause,
+    })?;
+
+    record(device, config, env, options)?;
+    let data = out_dir.join(DATA_FILE_NAME);
+    device
+        .adb(env)
+        .with_args(&["pull", DEVICE_DATA_PATH])
+        .with_arg(&data)
+        .run_and_wait()
+        .map_err(Error::PullFailed)?;
+
+    // Our lib is only stripped on its way into the APK, so the one in the
+    // target dir still has all its symbols.
+    let lib_path = device.target().lib_path(config, profile);
+    let lib_dir = lib_path
+        .parent()
+        .expect("developer error: lib path had no parent");
+    println!("Symbolizing...");
+    script(
+        device,
+        env,
+        &out_dir,
+        format!(
+            "python3 {} -i {} -lib {}",
+            util::shell_quote(simpleperf_dir.join("binary_cache_builder.py")),
+            DATA_FILE_NAME,
+            util::shell_quote(lib_dir),
+        ),
+    )
+    .run_and_wait()
+    .map_err(Error::BinaryCacheFailed)?;
+
+    let out = out_dir.join(options.format.file_name());
+    let report_sample = format!(
+        "python3 {} -i {} --symfs binary_cache",
+        util::shell_quote(simpleperf_dir.join("report_sample.py")),
+        DATA_FILE_NAME,
+    );
+    match options.format {
+        Format::Report => {
+            let output =
+                bossy::Command::pure(env.ndk.host_simpleperf_path().map_err(Error::MissingTool)?)
+                    .with_arg("report")
+                    .with_arg("-i")
+                    .with_arg(&data)
+                    .with_arg("--symfs")
+                    .with_arg(out_dir.join("binary_cache"))
+                    .run_and_wait_for_output()
+                    .map_err(Error::ReportFailed)?;
+            println!("{}", String::from_utf8_lossy(output.stdout()));
+            fs::write(&out, output.stdout()).map_err(|cause| Error::WriteFailed {
+                path: out.clone(),
+                cause,
+            })?;
+        }
+        Format::Html => {
+            script(
+                device,
+                env,
+                &out_dir,
+                format!(
+                    "python3 {} -i {} -o {} --no_browser",
+                    util::shell_quote(simpleperf_dir.join("report_html.py")),
+                    DATA_FILE_NAME,
+                    util::shell_quote(&out),
+                ),
+            )
+            .run_and_wait()
+            .map_err(Error::ReportFailed)?;
+        }
+        Format::PerfScript => {
+            script(
+                device,
+                env,
+                &out_dir,
+                format!("{} > {}", report_sample, util::shell_quote(&out)),
+            )
+            .run_and_wait()
+            .map_err(Error::ReportFailed)?;
+        }
+        Format::Flamegraph => {
+            script(
+                device,
+                env,
+                &out_dir,
+                // A pipeline would only fail if its last stage did, so each
+                // stage gets its own file instead.
+                format!(
+                    "{} > {} && inferno-collapse-perf {} > {} && inferno-flamegraph {} > {}",
+                    report_sample,
+                    PERF_SCRIPT_FILE_NAME,
+                    PERF_SCRIPT_FILE_NAME,
+                    FOLDED_FILE_NAME,
+                    FOLDED_FILE_NAME,
+                    util::shell_quote(&out)
+                ),
+            )
+            .run_and_wait()
+            .map_err(Error::FlamegraphFailed)?;
+        }
+    }
+    Ok(out)
+}
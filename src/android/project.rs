@@ -1,6 +1,14 @@
-use super::{config::Config, env::Env, ndk, target::Target};
+use super::{
+    config::{Config, Kind},
+    debug,
+    env::Env,
+    ndk,
+    target::Target,
+};
 use crate::{
-    dot_cargo,
+    dot_cargo, dot_vscode,
+    env::ExplicitEnv as _,
+    opts::Profile,
     target::TargetTrait as _,
     templating::{self, Pack},
     util::{
@@ -9,7 +17,11 @@ use crate::{
         ln,
     },
 };
-use std::{fs, path::PathBuf};
+use std::{
+    ffi::OsStr,
+    fs, io,
+    path::{Path, PathBuf},
+};
 
 pub static TEMPLATE_PACK: &str = "android-studio";
 
@@ -24,6 +36,26 @@ pub enum Error {
     },
     AssetDirSymlinkFailed(ln::Error),
     DotCargoGenFailed(ndk::MissingToolError),
+    DotVscodeGenFailed(ndk::MissingToolError),
+    ValidationLayersMissing {
+        path: PathBuf,
+    },
+    ValidationLayersArchiveUnsupported {
+        path: PathBuf,
+    },
+    ValidationLayersExtractFailed(bossy::Error),
+    ValidationLayersReadFailed {
+        path: PathBuf,
+        cause: io::Error,
+    },
+    ValidationLayersEmpty {
+        path: PathBuf,
+    },
+    ValidationLayersCopyFailed {
+        src: PathBuf,
+        dest: PathBuf,
+        cause: io::Error,
+    },
 }
 
 impl Reportable for Error {
@@ -35,7 +67,7 @@ impl Reportable for Error {
                 Report::error("Android template processing failed", err)
             }
             Self::DirectoryCreationFailed { path, cause } => Report::error(
-                format!("Failed to create Android assets directory at {:?}", path),
+                format!("Failed to create directory at {:?}", path),
                 cause,
             ),
             Self::AssetDirSymlinkFailed(err) => {
@@ -44,8 +76,149 @@ impl Reportable for Error {
             Self::DotCargoGenFailed(err) => {
                 Report::error("Failed to generate Android cargo config", err)
             }
+            Self::DotVscodeGenFailed(err) => {
+                Report::error("Failed to generate Android VS Code config", err)
+            }
+            Self::ValidationLayersMissing { path } => Report::action_request(
+                "Vulkan validation layers couldn't be found",
+                format!(
+                    "`{}.vulkan-validation-layers` is set to {:?}, which doesn't exist. You can download the layers from https://github.com/KhronosGroup/Vulkan-ValidationLayers/releases and point this at either the archive or the directory it extracts to, or set `{}.vulkan-validation` to false.",
+                    super::NAME,
+                    path,
+                    super::NAME,
+                ),
+            ),
+            Self::ValidationLayersArchiveUnsupported { path } => Report::error(
+                "Vulkan validation layers couldn't be extracted",
+                format!(
+                    "{:?} isn't a directory, a `.zip`, or a `.tar.gz`",
+                    path
+                ),
+            ),
+            Self::ValidationLayersExtractFailed(err) => {
+                Report::error("Vulkan validation layers couldn't be extracted", err)
+            }
+            Self::ValidationLayersReadFailed { path, cause } => Report::error(
+                format!("Failed to search for Vulkan validation layers in {:?}", path),
+                cause,
+            ),
+            Self::ValidationLayersEmpty { path } => Report::error(
+                "Vulkan validation layers couldn't be found",
+                format!(
+                    "{:?} doesn't contain any `libVkLayer_*.so` files in ABI directories (i.e. `arm64-v8a`)",
+                    path
+                ),
+            ),
+            Self::ValidationLayersCopyFailed { src, dest, cause } => Report::error(
+                format!(
+                    "Failed to copy Vulkan validation layer {:?} to {:?}",
+                    src, dest
+                ),
+                cause,
+            ),
+        }
+    }
+}
+
+fn find_validation_layers(dir: &Path, found: &mut Vec<(String, PathBuf)>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            find_validation_layers(&path, found)?;
+            continue;
+        }
+        let name = path.file_name().and_then(OsStr::to_str).unwrap_or_default();
+        let abi = path
+            .parent()
+            .and_then(Path::file_name)
+            .and_then(OsStr::to_str)
+            .unwrap_or_default();
+        if name.starts_with("libVkLayer_")
+            && name.ends_with(".so")
+            && Target::all().values().any(|target| target.abi == abi)
+        {
+            found.push((abi.to_owned(), path.clone()));
+        }
+    }
+    Ok(())
+}
+
+// Anything in the `debug` source set is left out of release builds, so we
+// don't have to worry about the layers shipping to users.
+fn install_validation_layers(config: &Config, env: &Env, layers: &Path) -> Result<(), Error> {
+    if !layers.exists() {
+        return Err(Error::ValidationLayersMissing {
+            path: layers.to_owned(),
+        });
+    }
+    let dir = if layers.is_dir() {
+        layers.to_owned()
+    } else {
+        let dest = util::temp_dir().join("vulkan-validation-layers");
+        if dest.is_dir() {
+            fs::remove_dir_all(&dest).map_err(|cause| Error::ValidationLayersReadFailed {
+                path: dest.clone(),
+                cause,
+            })?;
         }
+        fs::create_dir_all(&dest).map_err(|cause| Error::DirectoryCreationFailed {
+            path: dest.clone(),
+            cause,
+        })?;
+        let name = layers.to_string_lossy();
+        let command = if name.ends_with(".zip") {
+            bossy::Command::pure("unzip")
+                .with_arg("-q")
+                .with_arg(layers)
+                .with_arg("-d")
+                .with_arg(&dest)
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            bossy::Command::pure("tar")
+                .with_arg("-xzf")
+                .with_arg(layers)
+                .with_arg("-C")
+                .with_arg(&dest)
+        } else {
+            return Err(Error::ValidationLayersArchiveUnsupported {
+                path: layers.to_owned(),
+            });
+        };
+        command
+            .with_env_vars(env.explicit_env())
+            .run_and_wait()
+            .map_err(Error::ValidationLayersExtractFailed)?;
+        dest
+    };
+    let mut found = Vec::new();
+    find_validation_layers(&dir, &mut found).map_err(|cause| {
+        Error::ValidationLayersReadFailed {
+            path: dir.clone(),
+            cause,
+        }
+    })?;
+    if found.is_empty() {
+        return Err(Error::ValidationLayersEmpty {
+            path: layers.to_owned(),
+        });
     }
+    let jni_libs = config.project_dir().join("app/src/debug/jniLibs");
+    for (abi, src) in found {
+        let dest_dir = jni_libs.join(abi);
+        fs::create_dir_all(&dest_dir).map_err(|cause| Error::DirectoryCreationFailed {
+            path: dest_dir.clone(),
+            cause,
+        })?;
+        let dest = dest_dir.join(
+            src.file_name()
+                .expect("developer error: validation layer had no file name"),
+        );
+        fs::copy(&src, &dest).map_err(|cause| Error::ValidationLayersCopyFailed {
+            src,
+            dest,
+            cause,
+        })?;
+    }
+    Ok(())
 }
 
 pub fn gen(
@@ -54,6 +227,7 @@ pub fn gen(
     bike: &bicycle::Bicycle,
     filter: &templating::Filter,
     dot_cargo: &mut dot_cargo::DotCargo,
+    dot_vscode: &mut dot_vscode::DotVscode,
 ) -> Result<(), Error> {
     println!("Installing Android toolchains...");
     Target::install_all().map_err(Error::RustupFailed)?;
@@ -79,6 +253,25 @@ pub fn gen(
                     .map(|target| target.arch)
                     .collect::<Vec<_>>(),
             );
+            map.insert(
+                "abis",
+                Target::all()
+                    .values()
+                    .map(|target| target.abi)
+                    .collect::<Vec<_>>(),
+            );
+            map.insert("library", config.kind() == Kind::Library);
+            map.insert(
+                "java-source-dirs",
+                config
+                    .java_source_dirs()
+                    .map(|dir| util::relativize_path(dir, config.project_dir().join("app")))
+                    .collect::<Vec<_>>(),
+            );
+            map.insert("gradle-dependencies", config.gradle_dependencies());
+            map.insert("gradle-plugins", config.gradle_plugins());
+            map.insert("manifest-snippets", config.manifest_snippets());
+            map.insert("has-code", config.has_code());
         },
         filter.fun(),
     )
@@ -92,6 +285,11 @@ pub fn gen(
     ln::force_symlink_relative(config.app().asset_dir(), dest, ln::TargetStyle::Directory)
         .map_err(Error::AssetDirSymlinkFailed)?;
 
+    if let Some(layers) = config.vulkan_validation_layers() {
+        println!("Installing Vulkan validation layers...");
+        install_validation_layers(config, env, layers)?;
+    }
+
     {
         for target in Target::all().values() {
             dot_cargo.insert_target(
@@ -103,5 +301,119 @@ pub fn gen(
         }
     }
 
+    gen_vscode_config(config, env, dot_vscode).map_err(Error::DotVscodeGenFailed)?;
+
+    Ok(())
+}
+
+// rust-analyzer only checks one target at a time, so the env for every target
+// is keyed by triple (which is what `cc` and friends look for anyway). That
+// way, switching targets is just a matter of changing
+// `rust-analyzer.cargo.target`.
+fn analyzer_env(
+    config: &Config,
+    env: &Env,
+) -> Result<serde_json::Map<String, serde_json::Value>, ndk::MissingToolError> {
+    let mut analyzer_env = serde_json::Map::new();
+    analyzer_env.insert(
+        "ANDROID_SDK_ROOT".to_owned(),
+        env.sdk_root().to_string_lossy().into(),
+    );
+    analyzer_env.insert(
+        "NDK_HOME".to_owned(),
+        env.ndk.home().to_string_lossy().into(),
+    );
+    for target in Target::all().values() {
+        let triple_underscored = target.triple.replace('-', "_");
+        for (key, value) in target.cargo_env(config, env)? {
+            if key == "ANDROID_ABI" {
+                continue;
+            }
+            let key = key
+                .strip_prefix("TARGET_")
+                .map(|tool| format!("{}_{}", tool, triple_underscored))
+                .unwrap_or(key);
+            analyzer_env.insert(key, value.to_string_lossy().into());
+        }
+    }
+    Ok(analyzer_env)
+}
+
+fn gen_vscode_config(
+    config: &Config,
+    env: &Env,
+    dot_vscode: &mut dot_vscode::DotVscode,
+) -> Result<(), ndk::MissingToolError> {
+    use serde_json::json;
+    static DEBUG_TASK: &str = "cargo android debug";
+
+    let default_target = Target::all()
+        .get(Target::DEFAULT_KEY)
+        .expect("developer error: default target missing");
+    dot_vscode.insert_setting("rust-analyzer.cargo.target", default_target.triple.into());
+    dot_vscode.insert_setting(
+        "rust-analyzer.cargo.extraEnv",
+        analyzer_env(config, env)?.into(),
+    );
+
+    for subcommand in &["build", "run"] {
+        dot_vscode.insert_task(json!({
+            "label": format!("cargo android {}", subcommand),
+            "type": "shell",
+            "command": "cargo",
+            "args": ["android", subcommand],
+            "problemMatcher": ["$rustc"],
+        }));
+    }
+    // This keeps running for as long as `lldb-server` does, so VS Code needs
+    // to be told when it's ready for the debugger to attach.
+    dot_vscode.insert_task(json!({
+        "label": DEBUG_TASK,
+        "type": "shell",
+        "command": "cargo",
+        "args": [
+            "android",
+            "debug",
+            "--vscode",
+            "--port",
+            debug::DEFAULT_PORT,
+            "--jdwp-port",
+            debug::DEFAULT_JDWP_PORT,
+        ],
+        "isBackground": true,
+        "problemMatcher": {
+            "owner": "rust",
+            "pattern": { "regexp": "^error: (.*)$", "message": 1 },
+            "background": {
+                "activatesOnStart": true,
+                "beginsPattern": "^Starting `lldb-server`",
+                "endsPattern": "will keep running until you press Ctrl-C",
+            },
+        },
+    }));
+
+    let lib_paths = Target::all()
+        .values()
+        .map(|target| target.lib_path(config, Profile::Debug))
+        .collect::<Vec<_>>();
+    let symbol_dirs = lib_paths
+        .iter()
+        .map(|path| {
+            path.parent()
+                .expect("developer error: lib path had no parent")
+        })
+        .collect::<Vec<_>>();
+    dot_vscode.insert_launch_config(json!({
+        "name": "Debug on Android device",
+        "type": "lldb",
+        "request": "custom",
+        "preLaunchTask": DEBUG_TASK,
+        "initCommands": debug::init_commands(&env.ndk.sysroot()?, debug::DEFAULT_PORT, &symbol_dirs),
+        "processCreateCommands": [
+            format!("process attach --name {}", config.package_name()),
+            debug::release_command(debug::DEFAULT_JDWP_PORT),
+            "continue",
+        ],
+    }));
     Ok(())
 }
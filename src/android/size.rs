@@ -0,0 +1,459 @@
+use super::{config::Config, env::Env, ndk, target::Target};
+use crate::{
+    env::ExplicitEnv as _,
+    opts::{OutputFormat, Profile},
+    util::{
+        self,
+        cli::{Report, Reportable},
+    },
+};
+use once_cell_regex::regex;
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug)]
+pub enum Error {
+    ApkMissing { path: PathBuf },
+    ListFailed(bossy::Error),
+    TempDirCreationFailed { path: PathBuf, cause: io::Error },
+    ExtractFailed(bossy::Error),
+    MissingTool(ndk::MissingToolError),
+    SectionsFailed(bossy::Error),
+    SymbolsFailed(bossy::Error),
+    BuildIdFailed(bossy::Error),
+    SerializeFailed(serde_json::Error),
+}
+
+impl Reportable for Error {
+    fn report(&self) -> Report {
+        match self {
+            Self::ApkMissing { path } => Report::action_request(
+                format!("No APK found at {:?}", path),
+                "Run `cargo android run` to build one first, or pass `--apk` to point at an existing APK.",
+            ),
+            Self::ListFailed(err) => Report::error("Failed to list APK contents", err),
+            Self::TempDirCreationFailed { path, cause } => Report::error(
+                format!("Failed to create temporary directory {:?}", path),
+                cause,
+            ),
+            Self::ExtractFailed(err) => Report::error("Failed to extract lib from APK", err),
+            Self::MissingTool(err) => Report::error("Failed to locate required build tool", err),
+            Self::SectionsFailed(err) => Report::error("Failed to read ELF sections", err),
+            Self::SymbolsFailed(err) => Report::error("Failed to read ELF symbols", err),
+            Self::BuildIdFailed(err) => Report::error("Failed to read build ID", err),
+            Self::SerializeFailed(err) => Report::error("Failed to serialize size report", err),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+pub struct Size {
+    size: u64,
+    compressed: u64,
+}
+
+impl Size {
+    fn add(&mut self, other: Size) {
+        self.size += other.size;
+        self.compressed += other.compressed;
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Lib {
+    path: String,
+    abi: String,
+    size: Size,
+    sections: BTreeMap<String, u64>,
+    crates: Vec<(String, u64)>,
+    functions: Vec<(String, u64)>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Breakdown {
+    apk: PathBuf,
+    total: Size,
+    categories: BTreeMap<&'static str, Size>,
+    libs: Vec<Lib>,
+}
+
+fn category(name: &str) -> &'static str {
+    if name.starts_with("lib/") {
+        "native libs"
+    } else if name.starts_with("assets/") {
+        "assets"
+    } else if name.starts_with("res/") || name == "resources.arsc" {
+        "resources"
+    } else if name.starts_with("classes") && name.ends_with(".dex") {
+        "dex"
+    } else if name.starts_with("META-INF/") {
+        "signing"
+    } else {
+        "other"
+    }
+}
+
+// Methods from impls show up as `<krate::Type as Trait>::f`, so the leading
+// `<` (and any `&`, `mut` or `dyn` on the type) gets skipped, and they count
+// towards the crate of the type rather than the trait. Anything that doesn't
+// start with a path, like a slice or tuple type, ends up in `[other]`.
+fn crate_name(symbol: &str) -> &str {
+    let symbol = symbol
+        .trim_start_matches('<')
+        .trim_start_matches('&')
+        .trim_start_matches("mut ")
+        .trim_start_matches("dyn ");
+    match symbol.find("::") {
+        Some(end) if !symbol[..end].contains(|c: char| c == ' ' || c == '(') => &symbol[..end],
+        _ => "[other]",
+    }
+}
+
+fn strip_hash(symbol: &str) -> &str {
+    regex!(r"::h[0-9a-f]{16}$")
+        .find(symbol)
+        .map_or(symbol, |hash| &symbol[..hash.start()])
+}
+
+fn list_entries(env: &Env, apk: &Path) -> Result<Vec<(String, Size)>, Error> {
+    // `-l` adds the compressed size, which is the more honest number for
+    // download size.
+    let output = bossy::Command::pure("unzip")
+        .with_env_vars(env.explicit_env())
+        .with_args(&["-Z", "-l"])
+        .with_arg(apk)
+        .run_and_wait_for_output()
+        .map_err(Error::ListFailed)?;
+    Ok(String::from_utf8_lossy(output.stdout())
+        .lines()
+        .filter_map(|line| {
+            // The permissions column depends on which tool made the zip (it's
+            // `-rw----` for FAT-style entries, which AGP tends to write), so
+            // we go by the shape of the other columns instead.
+            let columns = line.split_whitespace().collect::<Vec<_>>();
+            if columns.len() < 10 || !regex!(r"^\d+\.\d+$").is_match(columns[1]) {
+                return None;
+            }
+            let size = columns[3].parse().ok()?;
+            let compressed = columns[5].parse().ok()?;
+            Some((columns[9..].join(" "), Size { size, compressed }))
+        })
+        .collect())
+}
+
+fn sections(env: &Env, lib: &Path) -> Result<BTreeMap<String, u64>, Error> {
+    let output = bossy::Command::pure(
+        env.ndk
+            .llvm_tool_path(ndk::LlvmTool::Size)
+            .map_err(Error::MissingTool)?,
+    )
+    .with_args(&["-A", "-d"])
+    .with_arg(lib)
+    .run_and_wait_for_output()
+    .map_err(Error::SectionsFailed)?;
+    Ok(String::from_utf8_lossy(output.stdout())
+        .lines()
+        .filter_map(|line| {
+            let mut columns = line.split_whitespace();
+            let name = columns.next().filter(|name| name.starts_with('.'))?;
+            let size = columns.next()?.parse().ok()?;
+            Some((name.to_owned(), size))
+        })
+        .collect())
+}
+
+fn symbols(env: &Env, lib: &Path) -> Result<Vec<(String, u64)>, Error> {
+    let output = bossy::Command::pure(
+        env.ndk
+            .llvm_tool_path(ndk::LlvmTool::Nm)
+            .map_err(Error::MissingTool)?,
+    )
+    .with_args(&[
+        "--print-size",
+        "--size-sort",
+        "--demangle",
+        "--defined-only",
+    ])
+    .with_arg(lib)
+    .run_and_wait_for_output()
+    .map_err(Error::SymbolsFailed)?;
+    Ok(String::from_utf8_lossy(output.stdout())
+        .lines()
+        .filter_map(|line| {
+            let mut columns = line.splitn(4, ' ');
+            let _addr = columns.next()?;
+            let size = u64::from_str_radix(columns.next()?, 16).ok()?;
+            let _ty = columns.next()?;
+            Some((strip_hash(columns.next()?).to_owned(), size))
+        })
+        .collect())
+}
+
+fn build_id(env: &Env, lib: &Path) -> Result<Option<String>, Error> {
+    let output = bossy::Command::pure(
+        env.ndk
+            .llvm_tool_path(ndk::LlvmTool::Readelf)
+            .map_err(Error::MissingTool)?,
+    )
+    .with_arg("--notes")
+    .with_arg(lib)
+    .run_and_wait_for_output()
+    .map_err(Error::BuildIdFailed)?;
+    Ok(regex!(r"Build ID: ([0-9a-fA-F]+)")
+        .captures(&String::from_utf8_lossy(output.stdout()))
+        .map(|caps| caps[1].to_owned()))
+}
+
+fn largest(sizes: impl Iterator<Item = (String, u64)>, top: usize) -> Vec<(String, u64)> {
+    let mut totals = BTreeMap::<String, u64>::new();
+    for (name, size) in sizes {
+        *totals.entry(name).or_default() += size;
+    }
+    let mut totals = totals.into_iter().collect::<Vec<_>>();
+    totals.sort_by(|(_, a), (_, b)| b.cmp(a));
+    totals.truncate(top);
+    totals
+}
+
+impl Breakdown {
+    // If the APK's libs are stripped, the unstripped libs from the last build
+    // are used for symbols instead, as long as they're the same build.
+    pub fn new(
+        config: &Config,
+        env: &Env,
+        profile: Profile,
+        apk: &Path,
+        top: usize,
+    ) -> Result<Self, Error> {
+        Self::analyze(env, apk, top, Some((config, profile)))
+    }
+
+    // The last build has nothing to do with some other APK, so this only ever
+    // uses what's in the APK itself.
+    pub fn for_comparison(env: &Env, apk: &Path, top: usize) -> Result<Self, Error> {
+        Self::analyze(env, apk, top, None)
+    }
+
+    fn analyze(
+        env: &Env,
+        apk: &Path,
+        top: usize,
+        last_build: Option<(&Config, Profile)>,
+    ) -> Result<Self, Error> {
+        if !apk.is_file() {
+            return Err(Error::ApkMissing {
+                path: apk.to_owned(),
+            });
+        }
+        let entries = list_entries(env, apk)?;
+        let mut total = Size::default();
+        let mut categories = BTreeMap::new();
+        for (name, size) in &entries {
+            total.add(*size);
+            categories
+                .entry(category(name))
+                .or_insert_with(Size::default)
+                .add(*size);
+        }
+        // Both sides of a comparison have libs with the same names, so they
+        // each get their own dir.
+        let temp_dir = util::temp_dir().join("size").join(if last_build.is_some() {
+            "current"
+        } else {
+            "compare"
+        });
+        fs::create_dir_all(&temp_dir).map_err(|cause| Error::TempDirCreationFailed {
+            path: temp_dir.clone(),
+            cause,
+        })?;
+        let mut libs = Vec::new();
+        for (name, size) in entries {
+            let abi = match regex!(r"^lib/([^/]+)/[^/]+\.so$").captures(&name) {
+                Some(caps) => caps[1].to_owned(),
+                None => continue,
+            };
+            bossy::Command::pure("unzip")
+                .with_env_vars(env.explicit_env())
+                .with_args(&["-o", "-q"])
+                .with_arg(apk)
+                .with_arg(&name)
+                .with_arg("-d")
+                .with_arg(&temp_dir)
+                .run_and_wait()
+                .map_err(Error::ExtractFailed)?;
+            let extracted = temp_dir.join(&name);
+            let mut functions = symbols(env, &extracted)?;
+            if functions.is_empty() {
+                // Release libs are stripped, so our best bet is the unstripped
+                // lib from the last build. That's only any help if the APK was
+                // actually made from it, which the build IDs tell us.
+                if let Some(unstripped) = last_build
+                    .and_then(|(config, profile)| {
+                        Target::for_abi(&abi).map(|target| target.lib_path(config, profile))
+                    })
+                    .filter(|path| path.file_name() == extracted.file_name() && path.is_file())
+                {
+                    let expected = build_id(env, &extracted)?;
+                    if expected.is_some() && build_id(env, &unstripped)? == expected {
+                        log::info!("{} has no symbols; using {:?} instead", name, unstripped);
+                        functions = symbols(env, &unstripped)?;
+                    } else {
+                        log::info!(
+                            "{} has no symbols, and its build ID doesn't match {:?}",
+                            name,
+                            unstripped
+                        );
+                    }
+                }
+            }
+            libs.push(Lib {
+                sections: sections(env, &extracted)?,
+                crates: largest(
+                    functions
+                        .iter()
+                        .map(|(symbol, size)| (crate_name(symbol).to_owned(), *size)),
+                    top,
+                ),
+                functions: largest(functions.into_iter(), top),
+                path: name,
+                abi,
+                size,
+            });
+        }
+        Ok(Self {
+            apk: apk.to_owned(),
+            total,
+            categories,
+            libs,
+        })
+    }
+}
+
+fn human(bytes: u64) -> String {
+    static UNITS: &[&str] = &["B", "KiB", "MiB", "GiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[unit])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+fn delta(new: u64, old: Option<u64>) -> String {
+    match old {
+        Some(old) if new > old => format!(" (+{})", human(new - old)),
+        Some(old) if new < old => format!(" (-{})", human(old - new)),
+        Some(_) => String::new(),
+        None => " (new)".to_owned(),
+    }
+}
+
+fn print_list(title: &str, entries: &[(String, u64)], base: Option<&[(String, u64)]>) {
+    if entries.is_empty() {
+        return;
+    }
+    println!("    {}:", title);
+    for (name, size) in entries {
+        let change = base
+            .map(|base| {
+                delta(
+                    *size,
+                    base.iter()
+                        .find(|(base_name, _)| base_name == name)
+                        .map(|(_, size)| *size),
+                )
+            })
+            .unwrap_or_default();
+        println!("      {:>10}  {}{}", human(*size), name, change);
+    }
+}
+
+pub fn report(
+    current: &Breakdown,
+    base: Option<&Breakdown>,
+    output_format: OutputFormat,
+) -> Result<(), Error> {
+    if output_format.json() {
+        #[derive(Serialize)]
+        struct Json<'a> {
+            current: &'a Breakdown,
+            base: Option<&'a Breakdown>,
+        }
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&Json { current, base })
+                .map_err(Error::SerializeFailed)?
+        );
+        return Ok(());
+    }
+    let change = |new: u64, old: Option<u64>| base.map(|_| delta(new, old)).unwrap_or_default();
+    println!(
+        "{}: {}{} ({} compressed{})",
+        current.apk.display(),
+        human(current.total.size),
+        change(current.total.size, base.map(|base| base.total.size)),
+        human(current.total.compressed),
+        change(
+            current.total.compressed,
+            base.map(|base| base.total.compressed)
+        ),
+    );
+    for (category, size) in &current.categories {
+        let base_size = base.and_then(|base| base.categories.get(category));
+        println!(
+            "  {:<12} {:>10}{}  {:>10} compressed{}",
+            category,
+            human(size.size),
+            change(size.size, base_size.map(|size| size.size)),
+            human(size.compressed),
+            change(size.compressed, base_size.map(|size| size.compressed)),
+        );
+    }
+    for lib in &current.libs {
+        let base_lib = base.and_then(|base| base.libs.iter().find(|base| base.path == lib.path));
+        // Comparing against a stripped lib would make every symbol look new.
+        let base_symbols = base_lib.filter(|lib| !lib.functions.is_empty());
+        println!(
+            "{}: {}{}",
+            lib.path,
+            human(lib.size.size),
+            change(lib.size.size, base_lib.map(|lib| lib.size.size)),
+        );
+        let mut sections = lib.sections.iter().collect::<Vec<_>>();
+        sections.sort_by(|(_, a), (_, b)| b.cmp(a));
+        let sections = sections
+            .into_iter()
+            .map(|(name, size)| (name.clone(), *size))
+            .collect::<Vec<_>>();
+        let base_sections = base_lib.map(|lib| {
+            lib.sections
+                .iter()
+                .map(|(name, size)| (name.clone(), *size))
+                .collect::<Vec<_>>()
+        });
+        print_list("sections", &sections, base_sections.as_deref());
+        print_list(
+            "largest crates",
+            &lib.crates,
+            base_symbols.map(|lib| lib.crates.as_slice()),
+        );
+        print_list(
+            "largest functions",
+            &lib.functions,
+            base_symbols.map(|lib| lib.functions.as_slice()),
+        );
+        if lib.functions.is_empty() {
+            println!("    -- no symbols available; the lib is stripped, and no unstripped build of it was found --");
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,279 @@
+use super::{config::Config, env::Env, ndk, target::Target};
+use crate::{
+    opts::{OutputFormat, Profile},
+    target::TargetTrait as _,
+    util::cli::{Report, Reportable},
+};
+use once_cell_regex::regex;
+use serde::Serialize;
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    str,
+};
+
+#[derive(Debug)]
+pub enum StacktraceError {
+    ReadFailed { path: PathBuf, cause: io::Error },
+    LogcatFailed(bossy::Error),
+    TombstoneReadFailed(bossy::Error),
+    TempDirCreationFailed { path: PathBuf, cause: io::Error },
+    BugreportFailed(bossy::Error),
+    UnzipFailed(bossy::Error),
+    MissingTool(ndk::MissingToolError),
+    SymbolizerFailed(bossy::Error),
+    InvalidUtf8(str::Utf8Error),
+    SerializeFailed(serde_json::Error),
+}
+
+impl Reportable for StacktraceError {
+    fn report(&self) -> Report {
+        match self {
+            Self::ReadFailed { path, cause } => {
+                Report::error(format!("Failed to read log file {:?}", path), cause)
+            }
+            Self::LogcatFailed(err) => Report::error("Failed to dump logcat output", err),
+            Self::TombstoneReadFailed(err) => Report::error("Failed to read tombstone", err),
+            Self::TempDirCreationFailed { path, cause } => Report::error(
+                format!("Failed to create temporary directory {:?}", path),
+                cause,
+            ),
+            Self::BugreportFailed(err) => Report::error("Failed to generate bugreport", err),
+            Self::UnzipFailed(err) => {
+                Report::error("Failed to extract tombstone from bugreport", err)
+            }
+            Self::MissingTool(err) => Report::error("Failed to locate required build tool", err),
+            Self::SymbolizerFailed(err) => Report::error("Failed to symbolicate stacktrace", err),
+            Self::InvalidUtf8(err) => Report::error(
+                "Failed to symbolicate stacktrace",
+                format!("`llvm-symbolizer` output contained invalid UTF-8: {}", err),
+            ),
+            Self::SerializeFailed(err) => Report::error("Failed to serialize stacktrace", err),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Frame {
+    index: u32,
+    pc: String,
+    lib: String,
+    // This is whatever symbol the crash dump itself provided, which is
+    // usually absent for stripped libs.
+    symbol: Option<String>,
+    function: Option<String>,
+    location: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Crash {
+    source: String,
+    abi: Option<String>,
+    signal: Option<String>,
+    abort_message: Option<String>,
+    frames: Vec<Frame>,
+}
+
+impl Crash {
+    // Works on both tombstones and logcat output, since the `DEBUG` lines in
+    // logcat are just a tombstone with a logcat prefix on each line. If there
+    // are multiple crashes, only the last one is kept.
+    pub fn parse(source: impl Into<String>, raw: &str) -> Option<Self> {
+        let last = raw
+            .rsplit("*** *** *** *** *** *** *** *** *** *** *** *** *** *** *** ***")
+            .next()
+            .unwrap_or(raw);
+        let frames = regex!(r"#(\d+)\s+pc\s+([0-9a-fA-F]+)\s+(\S+)(?:\s+\((.+?)\))?")
+            .captures_iter(last)
+            .filter_map(|caps| {
+                Some(Frame {
+                    index: caps[1].parse().ok()?,
+                    pc: caps[2].to_owned(),
+                    lib: caps[3].to_owned(),
+                    symbol: caps
+                        .get(4)
+                        .map(|symbol| symbol.as_str().to_owned())
+                        .filter(|symbol| !symbol.starts_with("offset ")),
+                    function: None,
+                    location: None,
+                })
+            })
+            .collect::<Vec<_>>();
+        if frames.is_empty() {
+            None
+        } else {
+            Some(Self {
+                source: source.into(),
+                abi: regex!(r"ABI: '(\w+)'")
+                    .captures(last)
+                    .map(|caps| caps[1].to_owned()),
+                signal: regex!(r"(signal \d+ \(\w+\).*)")
+                    .captures(last)
+                    .map(|caps| caps[1].trim().to_owned()),
+                abort_message: regex!(r"Abort message: '(.*)'")
+                    .captures(last)
+                    .map(|caps| caps[1].to_owned()),
+                frames,
+            })
+        }
+    }
+
+    fn target<'a>(&self, fallback: &'a Target<'a>) -> &'a Target<'a> {
+        self.abi
+            .as_deref()
+            .and_then(Target::for_arch)
+            .unwrap_or_else(|| {
+                log::info!(
+                    "no ABI was found in {}; assuming the crash was on {}",
+                    self.source,
+                    fallback.triple,
+                );
+                fallback
+            })
+    }
+
+    // Only our own lib gets symbolicated, since that's the only one we have
+    // an unstripped copy of.
+    fn symbolize(
+        &mut self,
+        config: &Config,
+        env: &Env,
+        profile: Profile,
+        fallback: &Target<'_>,
+    ) -> Result<(), StacktraceError> {
+        let lib_path = self.target(fallback).lib_path(config, profile);
+        if !lib_path.is_file() {
+            log::warn!(
+                "unstripped lib {:?} doesn't exist, so frames won't be symbolicated",
+                lib_path
+            );
+            return Ok(());
+        }
+        let so_name = lib_path
+            .file_name()
+            .expect("developer error: lib path had no file name")
+            .to_string_lossy()
+            .into_owned();
+        let frames = self
+            .frames
+            .iter_mut()
+            .filter(|frame| frame.lib.ends_with(&so_name))
+            .collect::<Vec<_>>();
+        if frames.is_empty() {
+            return Ok(());
+        }
+        let symbols = symbolize(
+            env,
+            &lib_path,
+            frames.iter().map(|frame| format!("0x{}", frame.pc)),
+        )?;
+        for (frame, (function, location)) in frames.into_iter().zip(symbols) {
+            frame.function = function;
+            frame.location = location;
+        }
+        Ok(())
+    }
+
+    fn print(&self) {
+        println!("{}:", self.source);
+        if let Some(signal) = &self.signal {
+            println!("  {}", signal);
+        }
+        if let Some(abort_message) = &self.abort_message {
+            println!("  Abort message: {}", abort_message);
+        }
+        for frame in &self.frames {
+            let lib = Path::new(&frame.lib)
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_else(|| frame.lib.clone());
+            match (&frame.function, &frame.symbol) {
+                (Some(function), _) => {
+                    println!("  #{:02} {} {}", frame.index, lib, function);
+                    if let Some(location) = &frame.location {
+                        println!("        at {}", location);
+                    }
+                }
+                (None, Some(symbol)) => println!("  #{:02} {} ({})", frame.index, lib, symbol),
+                (None, None) => println!("  #{:02} {} pc {}", frame.index, lib, frame.pc),
+            }
+        }
+    }
+}
+
+fn symbolize(
+    env: &Env,
+    lib_path: &Path,
+    addrs: impl Iterator<Item = String>,
+) -> Result<Vec<(Option<String>, Option<String>)>, StacktraceError> {
+    let output = bossy::Command::pure(
+        env.ndk
+            .llvm_tool_path(ndk::LlvmTool::Symbolizer)
+            .map_err(StacktraceError::MissingTool)?,
+    )
+    .with_arg(format!("--obj={}", lib_path.display()))
+    .with_args(&["--demangle", "--no-inlines", "--functions=linkage"])
+    .with_args(addrs)
+    .run_and_wait_for_output()
+    .map_err(StacktraceError::SymbolizerFailed)?;
+    let raw = output.stdout_str().map_err(StacktraceError::InvalidUtf8)?;
+    let known = |s: &str| Some(s.trim().to_owned()).filter(|s| !s.starts_with("??"));
+    Ok(raw
+        .split("\n\n")
+        .filter(|block| !block.trim().is_empty())
+        .map(|block| {
+            let mut lines = block.lines();
+            (lines.next().and_then(known), lines.next().and_then(known))
+        })
+        .collect())
+}
+
+pub fn report(
+    config: &Config,
+    env: &Env,
+    profile: Profile,
+    fallback: &Target<'_>,
+    mut crashes: Vec<Crash>,
+    output_format: OutputFormat,
+) -> Result<(), StacktraceError> {
+    for crash in &mut crashes {
+        crash.symbolize(config, env, profile, fallback)?;
+    }
+    if output_format.json() {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&crashes).map_err(StacktraceError::SerializeFailed)?
+        );
+    } else if crashes.is_empty() {
+        println!("  -- no stacktrace --");
+    } else {
+        for crash in &crashes {
+            crash.print();
+        }
+    }
+    Ok(())
+}
+
+pub fn from_file(
+    config: &Config,
+    env: &Env,
+    profile: Profile,
+    path: &Path,
+    output_format: OutputFormat,
+) -> Result<(), StacktraceError> {
+    let bytes = fs::read(path).map_err(|cause| StacktraceError::ReadFailed {
+        path: path.to_owned(),
+        cause,
+    })?;
+    let crashes = Crash::parse(path.display().to_string(), &String::from_utf8_lossy(&bytes))
+        .into_iter()
+        .collect();
+    report(
+        config,
+        env,
+        profile,
+        Target::default_ref(),
+        crashes,
+        output_format,
+    )
+}
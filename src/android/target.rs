@@ -5,16 +5,30 @@ use super::{
 };
 use crate::{
     dot_cargo::DotCargoTarget,
-    opts::{ForceColor, NoiseLevel, Profile},
+    opts::{ForceColor, NoiseLevel, Profile, StripSymbols},
     target::TargetTrait,
+    uniffi,
     util::{
         cli::{Report, Reportable},
-        ln, CargoCommand,
+        ln, CargoCommand, CargoPackage,
     },
 };
-use once_cell_regex::exports::once_cell::sync::OnceCell;
+use once_cell_regex::{exports::once_cell::sync::OnceCell, regex};
 use serde::Serialize;
-use std::{collections::BTreeMap, fmt, fs, io, path::PathBuf, str};
+use std::{
+    collections::BTreeMap,
+    ffi::OsString,
+    fmt, fs,
+    io::{self, Write as _},
+    path::{Path, PathBuf},
+    str,
+};
+
+// Relative to the project dir; the app's `build.gradle.kts` adds this to the
+// main source set.
+static UNIFFI_SOURCE_DIR: &str = "app/src/main/uniffi";
+// Where symbols go when the app version can't be determined.
+static UNKNOWN_VERSION: &str = "unknown";
 
 fn so_name(config: &Config) -> String {
     format!("lib{}.so", config.app().name_snake())
@@ -64,6 +78,26 @@ impl Reportable for CompileLibError {
     }
 }
 
+#[derive(Debug)]
+pub enum CargoPassthroughError {
+    MissingTool(ndk::MissingToolError),
+    CargoFailed {
+        subcommand: String,
+        cause: bossy::Error,
+    },
+}
+
+impl Reportable for CargoPassthroughError {
+    fn report(&self) -> Report {
+        match self {
+            Self::MissingTool(err) => Report::error("Failed to locate required build tool", err),
+            Self::CargoFailed { subcommand, cause } => {
+                Report::error(format!("Failed to run `cargo {}`", subcommand), cause)
+            }
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum LibSymlinkError {
     JniLibsSubDirCreationFailed(io::Error),
@@ -86,10 +120,72 @@ impl Reportable for LibSymlinkError {
     }
 }
 
+#[derive(Debug)]
+pub enum BundleSharedLibsError {
+    MissingTool(ndk::MissingToolError),
+    ReadelfFailed(bossy::Error),
+    InvalidUtf8(str::Utf8Error),
+    CopyFailed {
+        src: PathBuf,
+        dest: PathBuf,
+        cause: io::Error,
+    },
+}
+
+impl Reportable for BundleSharedLibsError {
+    fn report(&self) -> Report {
+        let msg = "Failed to bundle NDK shared libraries";
+        match self {
+            Self::MissingTool(err) => Report::error("Failed to locate required build tool", err),
+            Self::ReadelfFailed(err) => Report::error(msg, err),
+            Self::InvalidUtf8(err) => Report::error(
+                msg,
+                format!("`llvm-readelf` output contained invalid UTF-8: {}", err),
+            ),
+            Self::CopyFailed { src, dest, cause } => Report::error(
+                msg,
+                format!("Failed to copy {:?} to {:?}: {}", src, dest, cause),
+            ),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum StripError {
+    MissingTool(ndk::MissingToolError),
+    DirCreationFailed { path: PathBuf, cause: io::Error },
+    ObjcopyFailed(bossy::Error),
+    StripFailed(bossy::Error),
+    ReadelfFailed(bossy::Error),
+    BuildIdWriteFailed { path: PathBuf, cause: io::Error },
+}
+
+impl Reportable for StripError {
+    fn report(&self) -> Report {
+        let msg = "Failed to strip release lib";
+        match self {
+            Self::MissingTool(err) => Report::error("Failed to locate required build tool", err),
+            Self::DirCreationFailed { path, cause } => Report::error(
+                format!("Failed to create symbols directory at {:?}", path),
+                cause,
+            ),
+            Self::ObjcopyFailed(err) => Report::error("Failed to extract debug symbols", err),
+            Self::StripFailed(err) => Report::error(msg, err),
+            Self::ReadelfFailed(err) => Report::error("Failed to read build ID", err),
+            Self::BuildIdWriteFailed { path, cause } => {
+                Report::error(format!("Failed to record build ID in {:?}", path), cause)
+            }
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum BuildError {
     BuildFailed(CompileLibError),
     LibSymlinkFailed(LibSymlinkError),
+    BundleSharedLibsFailed(BundleSharedLibsError),
+    StripFailed(StripError),
+    UniffiFailed(uniffi::Error),
 }
 
 impl Reportable for BuildError {
@@ -97,6 +193,9 @@ impl Reportable for BuildError {
         match self {
             Self::BuildFailed(err) => err.report(),
             Self::LibSymlinkFailed(err) => err.report(),
+            Self::BundleSharedLibsFailed(err) => err.report(),
+            Self::StripFailed(err) => err.report(),
+            Self::UniffiFailed(err) => err.report(),
         }
     }
 }
@@ -183,6 +282,14 @@ impl<'a> Target<'a> {
         Self::all().values().find(|target| target.abi == abi)
     }
 
+    pub fn name(&self) -> &'a str {
+        Self::all()
+            .iter()
+            .find(|(_, target)| *target == self)
+            .map(|(name, _)| *name)
+            .expect("developer error: target missing from `Target::all`")
+    }
+
     pub fn generate_cargo_config(
         &self,
         config: &Config,
@@ -206,15 +313,95 @@ impl<'a> Target<'a> {
         Ok(DotCargoTarget {
             ar: Some(ar),
             linker: Some(linker),
-            rustflags: vec![
-                "-Clink-arg=-landroid".to_owned(),
-                "-Clink-arg=-llog".to_owned(),
-                "-Clink-arg=-lOpenSLES".to_owned(),
-            ],
+            rustflags: config
+                .link_libs()
+                .iter()
+                .map(|lib| format!("-Clink-arg=-l{}", lib))
+                .chain(config.rustflags(self.name()).iter().cloned())
+                .collect(),
         })
     }
 
-    fn compile_lib(
+    pub fn cargo_env(
+        &self,
+        config: &Config,
+        env: &Env,
+    ) -> Result<Vec<(String, OsString)>, ndk::MissingToolError> {
+        let min_sdk_version = config.min_sdk_version();
+        let triple_underscored = self.triple.replace('-', "_");
+        let sysroot = env.ndk.sysroot()?;
+        Ok(vec![
+            (
+                "ANDROID_NATIVE_API_LEVEL".to_owned(),
+                min_sdk_version.to_string().into(),
+            ),
+            (
+                "TARGET_AR".to_owned(),
+                env.ndk
+                    .binutil_path(ndk::Binutil::Ar, self.binutils_triple())?
+                    .into(),
+            ),
+            (
+                "TARGET_CC".to_owned(),
+                env.ndk
+                    .compiler_path(ndk::Compiler::Clang, self.clang_triple(), min_sdk_version)?
+                    .into(),
+            ),
+            (
+                "TARGET_CXX".to_owned(),
+                env.ndk
+                    .compiler_path(ndk::Compiler::Clangxx, self.clang_triple(), min_sdk_version)?
+                    .into(),
+            ),
+            // Like `TARGET_CC` and friends, the target-prefixed variant is
+            // used so that build scripts compiling for the host aren't handed
+            // the NDK toolchain.
+            (
+                "TARGET_CMAKE_TOOLCHAIN_FILE".to_owned(),
+                env.ndk.cmake_toolchain_path()?.into(),
+            ),
+            ("ANDROID_ABI".to_owned(), self.abi.into()),
+            (
+                "ANDROID_PLATFORM".to_owned(),
+                format!("android-{}", min_sdk_version).into(),
+            ),
+            (
+                format!("BINDGEN_EXTRA_CLANG_ARGS_{}", triple_underscored),
+                format!(
+                    "--sysroot={} --target={}{}",
+                    sysroot.display(),
+                    self.clang_triple(),
+                    min_sdk_version
+                )
+                .into(),
+            ),
+            // The NDK doesn't ship any `.pc` files, so this mostly serves to
+            // keep pkg-config from handing us libs built for the host.
+            (
+                format!("PKG_CONFIG_ALLOW_CROSS_{}", triple_underscored),
+                "1".into(),
+            ),
+            (
+                format!("PKG_CONFIG_SYSROOT_DIR_{}", triple_underscored),
+                sysroot.clone().into(),
+            ),
+            (
+                format!("PKG_CONFIG_LIBDIR_{}", triple_underscored),
+                format!(
+                    "{}:{}",
+                    sysroot
+                        .join("usr/lib")
+                        .join(self.binutils_triple())
+                        .join("pkgconfig")
+                        .display(),
+                    sysroot.join("usr/share/pkgconfig").display(),
+                )
+                .into(),
+            ),
+        ])
+    }
+
+    fn cargo(
         &self,
         config: &Config,
         metadata: &Metadata,
@@ -222,13 +409,12 @@ impl<'a> Target<'a> {
         noise_level: NoiseLevel,
         force_color: ForceColor,
         profile: Profile,
-        mode: CargoMode,
-    ) -> Result<(), CompileLibError> {
-        let min_sdk_version = config.min_sdk_version();
+        subcommand: &str,
+    ) -> Result<bossy::Command, ndk::MissingToolError> {
         // Force color, since gradle would otherwise give us uncolored output
         // (which Android Studio makes red, which is extra gross!)
         let color = if force_color.yes() { "always" } else { "auto" };
-        CargoCommand::new(mode.as_str())
+        Ok(CargoCommand::new(subcommand)
             .with_verbose(noise_level.pedantic())
             .with_package(Some(config.app().name()))
             .with_manifest_path(Some(config.app().manifest_path()))
@@ -237,28 +423,62 @@ impl<'a> Target<'a> {
             .with_features(metadata.features())
             .with_release(profile.release())
             .into_command_pure(env)
-            .with_env_var("ANDROID_NATIVE_API_LEVEL", min_sdk_version.to_string())
-            .with_env_var(
-                "TARGET_AR",
-                env.ndk
-                    .binutil_path(ndk::Binutil::Ar, self.binutils_triple())
-                    .map_err(CompileLibError::MissingTool)?,
-            )
-            .with_env_var(
-                "TARGET_CC",
-                env.ndk
-                    .compiler_path(ndk::Compiler::Clang, self.clang_triple(), min_sdk_version)
-                    .map_err(CompileLibError::MissingTool)?,
-            )
-            .with_env_var(
-                "TARGET_CXX",
-                env.ndk
-                    .compiler_path(ndk::Compiler::Clangxx, self.clang_triple(), min_sdk_version)
-                    .map_err(CompileLibError::MissingTool)?,
-            )
-            .with_args(&["--color", color])
-            .run_and_wait()
-            .map_err(|cause| CompileLibError::CargoFailed { mode, cause })?;
+            .with_env_vars(self.cargo_env(config, env)?)
+            .with_args(&["--color", color]))
+    }
+
+    fn compile_lib(
+        &self,
+        config: &Config,
+        metadata: &Metadata,
+        env: &Env,
+        noise_level: NoiseLevel,
+        force_color: ForceColor,
+        profile: Profile,
+        mode: CargoMode,
+    ) -> Result<(), CompileLibError> {
+        self.cargo(
+            config,
+            metadata,
+            env,
+            noise_level,
+            force_color,
+            profile,
+            mode.as_str(),
+        )
+        .map_err(CompileLibError::MissingTool)?
+        .run_and_wait()
+        .map_err(|cause| CompileLibError::CargoFailed { mode, cause })?;
+        Ok(())
+    }
+
+    pub fn cargo_passthrough(
+        &self,
+        config: &Config,
+        metadata: &Metadata,
+        env: &Env,
+        noise_level: NoiseLevel,
+        force_color: ForceColor,
+        profile: Profile,
+        subcommand: &str,
+        args: &[String],
+    ) -> Result<(), CargoPassthroughError> {
+        self.cargo(
+            config,
+            metadata,
+            env,
+            noise_level,
+            force_color,
+            profile,
+            subcommand,
+        )
+        .map_err(CargoPassthroughError::MissingTool)?
+        .with_args(args)
+        .run_and_wait()
+        .map_err(|cause| CargoPassthroughError::CargoFailed {
+            subcommand: subcommand.to_owned(),
+            cause,
+        })?;
         Ok(())
     }
 
@@ -290,18 +510,33 @@ impl<'a> Target<'a> {
         Ok(())
     }
 
-    fn symlink_lib(&self, config: &Config, profile: Profile) -> Result<(), LibSymlinkError> {
-        self.make_jnilibs_subdir(config)
-            .map_err(LibSymlinkError::JniLibsSubDirCreationFailed)?;
-        let so_name = so_name(config);
-        let src = config.app().prefix_path(format!(
+    pub(super) fn apk_path(&self, config: &Config, profile: Profile) -> PathBuf {
+        let build_ty = profile.as_str();
+        let apk_suffix = match profile {
+            Profile::Debug => build_ty,
+            // TODO: how to handle signed APKs?
+            Profile::Release => "release-unsigned",
+        };
+        config.project_dir().join(format!(
+            "app/build/outputs/apk/{}/{}/app-{}-{}.apk",
+            self.arch, build_ty, self.arch, apk_suffix
+        ))
+    }
+
+    pub(super) fn lib_path(&self, config: &Config, profile: Profile) -> PathBuf {
+        config.app().prefix_path(format!(
             "target/{}/{}/{}",
             &self.triple,
             profile.as_str(),
-            &so_name
-        ));
+            so_name(config)
+        ))
+    }
+
+    fn symlink_lib(&self, config: &Config, src: PathBuf) -> Result<(), LibSymlinkError> {
+        self.make_jnilibs_subdir(config)
+            .map_err(LibSymlinkError::JniLibsSubDirCreationFailed)?;
         if src.exists() {
-            let dest = self.get_jnilibs_subdir(config).join(&so_name);
+            let dest = self.get_jnilibs_subdir(config).join(so_name(config));
             ln::force_symlink(src, dest, ln::TargetStyle::File)
                 .map_err(LibSymlinkError::SymlinkFailed)
         } else {
@@ -309,6 +544,163 @@ impl<'a> Target<'a> {
         }
     }
 
+    // The version could be inherited from a workspace, so this has to ask
+    // Cargo. Not knowing it isn't worth failing the build over, so the symbols
+    // just get filed under a placeholder in that case.
+    fn app_version(config: &Config) -> String {
+        CargoPackage::load(&config.app().manifest_path())
+            .map(|package| package.version)
+            .unwrap_or_else(|err| {
+                log::warn!(
+                    "failed to get app version, so symbols will be filed under {:?}: {}",
+                    UNKNOWN_VERSION,
+                    err
+                );
+                UNKNOWN_VERSION.to_owned()
+            })
+    }
+
+    fn build_id(&self, env: &Env, lib: &Path) -> Result<Option<String>, StripError> {
+        let output = bossy::Command::pure(
+            env.ndk
+                .llvm_tool_path(ndk::LlvmTool::Readelf)
+                .map_err(StripError::MissingTool)?,
+        )
+        .with_arg("--notes")
+        .with_arg(lib)
+        .run_and_wait_for_output()
+        .map_err(StripError::ReadelfFailed)?;
+        Ok(regex!(r"Build ID: ([0-9a-fA-F]+)")
+            .captures(&String::from_utf8_lossy(output.stdout()))
+            .map(|caps| caps[1].to_owned()))
+    }
+
+    // The debug info goes into `symbols/<version>/<abi>/<build-id>/` in the
+    // app root, so crashes from any released build can be symbolicated later
+    // on, even if the same version was built more than once. Returns the path
+    // to the stripped lib.
+    fn strip_lib(
+        &self,
+        config: &Config,
+        env: &Env,
+        profile: Profile,
+    ) -> Result<PathBuf, StripError> {
+        let src = self.lib_path(config, profile);
+        let so_name = so_name(config);
+        let symbols_dir = config
+            .app()
+            .prefix_path("symbols")
+            .join(Self::app_version(config))
+            .join(self.abi);
+        let build_id = self.build_id(env, &src)?.unwrap_or_else(|| {
+            log::warn!("{:?} has no build ID", src);
+            "none".to_owned()
+        });
+        let debug_dir = symbols_dir.join(&build_id);
+        fs::create_dir_all(&debug_dir).map_err(|cause| StripError::DirCreationFailed {
+            path: debug_dir.clone(),
+            cause,
+        })?;
+        let debug_path = debug_dir.join(format!("{}.debug", so_name));
+        let objcopy = env
+            .ndk
+            .llvm_tool_path(ndk::LlvmTool::Objcopy)
+            .map_err(StripError::MissingTool)?;
+        bossy::Command::pure(&objcopy)
+            .with_arg("--only-keep-debug")
+            .with_arg(&src)
+            .with_arg(&debug_path)
+            .run_and_wait()
+            .map_err(StripError::ObjcopyFailed)?;
+        let stripped_dir = src
+            .parent()
+            .expect("developer error: lib path had no parent")
+            .join("stripped");
+        fs::create_dir_all(&stripped_dir).map_err(|cause| StripError::DirCreationFailed {
+            path: stripped_dir.clone(),
+            cause,
+        })?;
+        let stripped = stripped_dir.join(&so_name);
+        bossy::Command::pure(
+            env.ndk
+                .llvm_tool_path(ndk::LlvmTool::Strip)
+                .map_err(StripError::MissingTool)?,
+        )
+        .with_arg("--strip-all")
+        .with_arg(&src)
+        .with_arg("-o")
+        .with_arg(&stripped)
+        .run_and_wait()
+        .map_err(StripError::StripFailed)?;
+        bossy::Command::pure(&objcopy)
+            .with_arg(format!("--add-gnu-debuglink={}", debug_path.display()))
+            .with_arg(&stripped)
+            .run_and_wait()
+            .map_err(StripError::ObjcopyFailed)?;
+        let build_ids_path = symbols_dir.join("build-ids.txt");
+        let entry = format!("{} {}", build_id, so_name);
+        let already_recorded = fs::read_to_string(&build_ids_path)
+            .map(|build_ids| build_ids.lines().any(|line| line == entry))
+            .unwrap_or_default();
+        if !already_recorded {
+            fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&build_ids_path)
+                .and_then(|mut file| writeln!(file, "{}", entry))
+                .map_err(|cause| StripError::BuildIdWriteFailed {
+                    path: build_ids_path,
+                    cause,
+                })?;
+        }
+        println!("Wrote debug symbols for {} to {:?}", self.abi, debug_path);
+        Ok(stripped)
+    }
+
+    // Libs like `libc++_shared.so` aren't present on devices, so they have to be
+    // shipped alongside our lib. Those live directly in the sysroot's lib dir,
+    // while the stubs for system libs live in API level subdirs.
+    fn bundle_shared_libs(
+        &self,
+        config: &Config,
+        env: &Env,
+        profile: Profile,
+    ) -> Result<(), BundleSharedLibsError> {
+        let output = bossy::Command::pure(
+            env.ndk
+                .llvm_tool_path(ndk::LlvmTool::Readelf)
+                .map_err(BundleSharedLibsError::MissingTool)?,
+        )
+        .with_arg("--dynamic-table")
+        .with_arg(self.lib_path(config, profile))
+        .run_and_wait_for_output()
+        .map_err(BundleSharedLibsError::ReadelfFailed)?;
+        let raw = output
+            .stdout_str()
+            .map_err(BundleSharedLibsError::InvalidUtf8)?;
+        let lib_dir = env
+            .ndk
+            .sysroot()
+            .map_err(BundleSharedLibsError::MissingTool)?
+            .join("usr/lib")
+            .join(self.binutils_triple());
+        let system_lib_dir = lib_dir.join(config.min_sdk_version().to_string());
+        for caps in regex!(r"\(NEEDED\)\s+Shared library: \[(.+)\]").captures_iter(raw) {
+            let needed = &caps[1];
+            let src = lib_dir.join(needed);
+            if src.is_file() && !system_lib_dir.join(needed).exists() {
+                let dest = self.get_jnilibs_subdir(config).join(needed);
+                log::info!("bundling NDK shared lib {:?} into {:?}", src, dest);
+                fs::copy(&src, &dest).map_err(|cause| BundleSharedLibsError::CopyFailed {
+                    src,
+                    dest,
+                    cause,
+                })?;
+            }
+        }
+        Ok(())
+    }
+
     pub fn check(
         &self,
         config: &Config,
@@ -336,6 +728,7 @@ impl<'a> Target<'a> {
         noise_level: NoiseLevel,
         force_color: ForceColor,
         profile: Profile,
+        strip_symbols: StripSymbols,
     ) -> Result<(), BuildError> {
         self.compile_lib(
             config,
@@ -347,7 +740,32 @@ impl<'a> Target<'a> {
             CargoMode::Build,
         )
         .map_err(BuildError::BuildFailed)?;
-        self.symlink_lib(config, profile)
-            .map_err(BuildError::LibSymlinkFailed)
+        let strip = strip_symbols.yes() || config.strip_release_libs();
+        let lib = if strip && profile.release() {
+            self.strip_lib(config, env, profile)
+                .map_err(BuildError::StripFailed)?
+        } else {
+            if strip_symbols.yes() {
+                log::warn!("only release builds are stripped; ignoring `--strip-symbols`");
+            }
+            self.lib_path(config, profile)
+        };
+        self.symlink_lib(config, lib)
+            .map_err(BuildError::LibSymlinkFailed)?;
+        if config.bundle_shared_libs() {
+            self.bundle_shared_libs(config, env, profile)
+                .map_err(BuildError::BundleSharedLibsFailed)?;
+        }
+        if config.app().uniffi() {
+            uniffi::generate(
+                config.app(),
+                env,
+                self.lib_path(config, profile),
+                uniffi::Language::Kotlin,
+                config.project_dir().join(UNIFFI_SOURCE_DIR),
+            )
+            .map_err(BuildError::UniffiFailed)?;
+        }
+        Ok(())
     }
 }
@@ -2,10 +2,11 @@ use crate::{
     apple::{
         config::{Config, Metadata},
         device::{Device, RunError},
-        ios_deploy, rust_version_check,
+        ios_deploy, project, rust_version_check,
         target::{ArchiveError, BuildError, CheckError, CompileLibError, ExportError, Target},
         NAME,
     },
+    cbindgen,
     config::{
         metadata::{self, Metadata as OmniMetadata},
         Config as OmniConfig, LoadOrGenError,
@@ -14,14 +15,15 @@ use crate::{
     device::PromptError,
     env::{Env, Error as EnvError},
     opts, os,
-    target::{call_for_targets_with_fallback, TargetInvalid, TargetTrait as _},
+    target::{call_for_targets_with_defaults, TargetInvalid, TargetTrait as _},
+    uniffi,
     util::{
         self,
         cli::{self, Exec, GlobalFlags, Report, Reportable, TextWrapper, VERSION_INFO},
         prompt,
     },
 };
-use std::{collections::HashMap, ffi::OsStr, path::PathBuf};
+use std::{collections::HashMap, ffi::OsStr, io, path::PathBuf};
 use structopt::{clap::AppSettings, StructOpt};
 
 #[derive(Debug, StructOpt)]
@@ -62,20 +64,35 @@ pub enum Command {
     Open,
     #[structopt(name = "check", about = "Checks if code compiles for target(s)")]
     Check {
-        #[structopt(name = "targets", default_value = Target::DEFAULT_KEY, possible_values = Target::name_list())]
+        #[structopt(name = "targets", possible_values = Target::name_list())]
         targets: Vec<String>,
+        #[structopt(
+            long = "device-target",
+            help = "Use the target of a connected device when no targets are given"
+        )]
+        device_target: bool,
     },
     #[structopt(name = "build", about = "Builds static libraries for target(s)")]
     Build {
-        #[structopt(name = "targets", default_value = Target::DEFAULT_KEY, possible_values = Target::name_list())]
+        #[structopt(name = "targets", possible_values = Target::name_list())]
         targets: Vec<String>,
+        #[structopt(
+            long = "device-target",
+            help = "Use the target of a connected device when no targets are given"
+        )]
+        device_target: bool,
         #[structopt(flatten)]
         profile: cli::Profile,
     },
     #[structopt(name = "archive", about = "Builds and archives for targets(s)")]
     Archive {
-        #[structopt(name = "targets", default_value = Target::DEFAULT_KEY, possible_values = Target::name_list())]
+        #[structopt(name = "targets", possible_values = Target::name_list())]
         targets: Vec<String>,
+        #[structopt(
+            long = "device-target",
+            help = "Use the target of a connected device when no targets are given"
+        )]
+        device_target: bool,
         #[structopt(flatten)]
         profile: cli::Profile,
     },
@@ -146,6 +163,11 @@ pub enum Error {
     MacosSdkRootInvalid { macos_sdk_root: PathBuf },
     ArchInvalid { arch: String },
     CompileLibFailed(CompileLibError),
+    UniffiFailed(uniffi::Error),
+    XcodeProjectReadFailed(io::Error),
+    XcodegenFailed(bossy::Error),
+    UniffiFilesAdded,
+    CbindgenFailed(cbindgen::Error),
 }
 
 impl Reportable for Error {
@@ -188,6 +210,16 @@ impl Reportable for Error {
                 format!("{:?} isn't a known arch", arch),
             ),
             Self::CompileLibFailed(err) => err.report(),
+            Self::UniffiFailed(err) => err.report(),
+            Self::XcodeProjectReadFailed(err) => {
+                Report::error("Failed to check Xcode project for UniFFI bindings", err)
+            }
+            Self::XcodegenFailed(err) => Report::error("Failed to run `xcodegen`", err),
+            Self::UniffiFilesAdded => Report::action_request(
+                "UniFFI generated new binding files, so the Xcode project was regenerated to include them",
+                "Xcode had already planned out this build by the time they showed up, so just build again.",
+            ),
+            Self::CbindgenFailed(err) => err.report(),
         }
     }
 }
@@ -254,11 +286,16 @@ impl Exec for Input {
                     open_in_xcode(config)
                 })
             }
-            Command::Check { targets } => {
+            Command::Check {
+                targets,
+                device_target,
+            } => {
                 version_check()?;
                 with_config(non_interactive, wrapper, |config, metadata| {
-                    call_for_targets_with_fallback(
+                    call_for_targets_with_defaults(
                         targets.iter(),
+                        config.default_targets(),
+                        device_target,
                         &detect_target_ok,
                         &env,
                         |target: &Target| {
@@ -272,12 +309,15 @@ impl Exec for Input {
             }
             Command::Build {
                 targets,
+                device_target,
                 profile: cli::Profile { profile },
             } => with_config(non_interactive, wrapper, |config, _| {
                 version_check()?;
                 ensure_init(config)?;
-                call_for_targets_with_fallback(
+                call_for_targets_with_defaults(
                     targets.iter(),
+                    config.default_targets(),
+                    device_target,
                     &detect_target_ok,
                     &env,
                     |target: &Target| {
@@ -290,12 +330,15 @@ impl Exec for Input {
             }),
             Command::Archive {
                 targets,
+                device_target,
                 profile: cli::Profile { profile },
             } => with_config(non_interactive, wrapper, |config, _| {
                 version_check()?;
                 ensure_init(config)?;
-                call_for_targets_with_fallback(
+                call_for_targets_with_defaults(
                     targets.iter(),
+                    config.default_targets(),
+                    device_target,
                     &detect_target_ok,
                     &env,
                     |target: &Target| {
@@ -348,6 +391,17 @@ impl Exec for Input {
                     return Err(Error::IncludeDirInvalid { include_dir });
                 }
 
+                // This runs before the app's sources are compiled, so the
+                // header is always current.
+                if config.cbindgen() {
+                    cbindgen::generate(
+                        config.app().root_dir(),
+                        &env,
+                        config.bindings_header_path(),
+                    )
+                    .map_err(Error::CbindgenFailed)?;
+                }
+
                 let mut host_env = HashMap::<&str, &OsStr>::new();
 
                 // Host flags that are used by build scripts
@@ -374,6 +428,7 @@ impl Exec for Input {
 
                 let isysroot = format!("-isysroot {}", sdk_root.display());
 
+                let mut lib = None;
                 for arch in arches {
                     // Set target-specific flags
                     let triple = match arch.as_str() {
@@ -407,6 +462,26 @@ impl Exec for Input {
                             target_env,
                         )
                         .map_err(Error::CompileLibFailed)?;
+                    lib = Some(target.lib_path(config, profile));
+                }
+
+                // The bindings are the same for every arch, so we only need
+                // to run bindgen once.
+                if let Some(lib) = lib.filter(|_| config.app().uniffi()) {
+                    uniffi::generate(
+                        config.app(),
+                        &env,
+                        lib,
+                        uniffi::Language::Swift,
+                        config.uniffi_dir(),
+                    )
+                    .map_err(Error::UniffiFailed)?;
+                    if project::has_new_files(config, &config.uniffi_dir())
+                        .map_err(Error::XcodeProjectReadFailed)?
+                    {
+                        project::xcodegen(config).map_err(Error::XcodegenFailed)?;
+                        return Err(Error::UniffiFilesAdded);
+                    }
                 }
                 Ok(())
             }),
@@ -2,8 +2,10 @@ mod raw;
 
 pub use self::raw::*;
 
+use super::target::Target;
 use crate::{
     config::app::App,
+    target::TargetTrait as _,
     util::{self, cli::Report},
 };
 use serde::{Deserialize, Serialize};
@@ -13,6 +15,7 @@ use std::{
 };
 
 static DEFAULT_PROJECT_DIR: &str = "gen/apple";
+const DEFAULT_CBINDGEN: bool = false;
 
 #[derive(Debug, Default, Deserialize)]
 pub struct Features {
@@ -105,6 +108,8 @@ pub enum Error {
     DevelopmentTeamMissing,
     DevelopmentTeamEmpty,
     ProjectDirInvalid(ProjectDirInvalid),
+    DefaultTargetsEmpty,
+    DefaultTargetInvalid { target: String },
 }
 
 impl Error {
@@ -121,6 +126,23 @@ impl Error {
                 msg,
                 format!("`{}.project-dir` invalid: {}", super::NAME, err),
             ),
+            Self::DefaultTargetsEmpty => Report::error(
+                msg,
+                format!(
+                    "`{}.default-targets` is empty; remove it to use the default, or list at least one of {:?}",
+                    super::NAME,
+                    Target::name_list(),
+                ),
+            ),
+            Self::DefaultTargetInvalid { target } => Report::error(
+                msg,
+                format!(
+                    "`{}.default-targets` contains {:?}, which isn't a valid target; the possible targets are {:?}",
+                    super::NAME,
+                    target,
+                    Target::name_list(),
+                ),
+            ),
         }
     }
 }
@@ -132,6 +154,8 @@ pub struct Config {
     app: App,
     development_team: String,
     project_dir: String,
+    cbindgen: bool,
+    default_targets: Vec<String>,
 }
 
 impl Config {
@@ -169,10 +193,41 @@ impl Config {
                 Ok(DEFAULT_PROJECT_DIR.to_owned())
             })?;
 
+        let cbindgen = raw.cbindgen.unwrap_or_else(|| {
+            log::info!(
+                "`{}.cbindgen` not set; defaulting to {}",
+                super::NAME,
+                DEFAULT_CBINDGEN
+            );
+            DEFAULT_CBINDGEN
+        });
+
+        let default_targets = raw.default_targets.unwrap_or_else(|| {
+            log::info!(
+                "`{}.default-targets` not set; defaulting to [{:?}]",
+                super::NAME,
+                Target::DEFAULT_KEY
+            );
+            vec![Target::DEFAULT_KEY.to_owned()]
+        });
+        if default_targets.is_empty() {
+            return Err(Error::DefaultTargetsEmpty);
+        }
+        if let Some(target) = default_targets
+            .iter()
+            .find(|target| Target::for_name(target).is_none())
+        {
+            return Err(Error::DefaultTargetInvalid {
+                target: target.clone(),
+            });
+        }
+
         Ok(Self {
             app,
             development_team: raw.development_team,
             project_dir,
+            cbindgen,
+            default_targets,
         })
     }
 
@@ -188,6 +243,32 @@ impl Config {
         self.project_dir().is_dir()
     }
 
+    pub fn default_targets(&self) -> &[String] {
+        &self.default_targets
+    }
+
+    pub fn cbindgen(&self) -> bool {
+        self.cbindgen
+    }
+
+    // Only meaningful when `cbindgen` is on; otherwise, this header is
+    // maintained by hand.
+    pub fn bindings_header_path(&self) -> PathBuf {
+        self.project_dir()
+            .join(format!("Sources/{}/bindings/bindings.h", self.app.name()))
+    }
+
+    // Only meaningful when `app.uniffi` is on; bindings are regenerated into
+    // this dir on every build.
+    pub fn uniffi_dir(&self) -> PathBuf {
+        self.project_dir().join("Sources/uniffi")
+    }
+
+    pub fn pbxproj_path(&self) -> PathBuf {
+        self.project_dir()
+            .join(format!("{}.xcodeproj/project.pbxproj", self.app.name()))
+    }
+
     pub fn workspace_path(&self) -> PathBuf {
         self.project_dir().join(format!(
             "{}.xcodeproj/project.xcworkspace/",
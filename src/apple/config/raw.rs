@@ -51,6 +51,8 @@ pub struct Raw {
     pub ios_features: Option<Vec<String>>,
     pub macos_no_default_features: Option<bool>,
     pub macos_features: Option<Vec<String>>,
+    pub cbindgen: Option<bool>,
+    pub default_targets: Option<Vec<String>>,
 }
 
 impl Raw {
@@ -67,6 +69,8 @@ impl Raw {
             ios_features: None,
             macos_no_default_features: None,
             macos_features: None,
+            cbindgen: None,
+            default_targets: None,
         })
     }
 
@@ -142,6 +146,8 @@ impl Raw {
             ios_features: None,
             macos_no_default_features: None,
             macos_features: None,
+            cbindgen: None,
+            default_targets: None,
         })
     }
 }
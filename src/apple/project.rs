@@ -1,5 +1,7 @@
 use super::{config::Config, deps, rust_version_check, target::Target};
 use crate::{
+    cbindgen,
+    env::{Env, Error as EnvError},
     opts,
     target::TargetTrait as _,
     templating::{self, Pack},
@@ -9,7 +11,10 @@ use crate::{
         ln,
     },
 };
-use std::path::{Path, PathBuf};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
 
 pub static TEMPLATE_PACK: &str = "xcode";
 
@@ -21,6 +26,8 @@ pub enum Error {
     MissingPack(templating::LookupError),
     TemplateProcessingFailed(bicycle::ProcessingError),
     AssetDirSymlinkFailed(ln::Error),
+    EnvInitFailed(EnvError),
+    CbindgenFailed(cbindgen::Error),
     XcodegenFailed(bossy::Error),
 }
 
@@ -39,6 +46,8 @@ impl Reportable for Error {
             Self::AssetDirSymlinkFailed(err) => {
                 Report::error("Asset dir couldn't be symlinked into Xcode project", err)
             }
+            Self::EnvInitFailed(err) => err.report(),
+            Self::CbindgenFailed(err) => err.report(),
             Self::XcodegenFailed(err) => Report::error("Failed to run `xcodegen`", err),
         }
     }
@@ -87,13 +96,37 @@ pub fn gen(
     ln::force_symlink_relative(config.app().asset_dir(), &dest, ln::TargetStyle::Directory)
         .map_err(Error::AssetDirSymlinkFailed)?;
 
-    // Note that Xcode doesn't always reload the project nicely; reopening is
-    // often necessary.
+    if config.cbindgen() {
+        println!("Generating C header...");
+        let env = Env::new().map_err(Error::EnvInitFailed)?;
+        cbindgen::generate(config.app().root_dir(), &env, config.bindings_header_path())
+            .map_err(Error::CbindgenFailed)?;
+    }
+
     println!("Generating Xcode project...");
+    xcodegen(config).map_err(Error::XcodegenFailed)?;
+    Ok(())
+}
+
+// Note that Xcode doesn't always reload the project nicely; reopening is
+// often necessary.
+pub fn xcodegen(config: &Config) -> bossy::Result<()> {
     bossy::Command::impure("xcodegen")
         .with_args(&["generate", "--spec"])
-        .with_arg(dest.join("project.yml"))
-        .run_and_wait()
-        .map_err(Error::XcodegenFailed)?;
+        .with_arg(config.project_dir().join("project.yml"))
+        .run_and_wait()?;
     Ok(())
 }
+
+// Xcode only compiles the files that were around when the project was
+// generated, so this checks if anything in `dir` came along since then.
+pub fn has_new_files(config: &Config, dir: &Path) -> io::Result<bool> {
+    let pbxproj = fs::read_to_string(config.pbxproj_path())?;
+    for entry in fs::read_dir(dir)? {
+        let file_name = entry?.file_name();
+        if !pbxproj.contains(&format!("/* {} */", file_name.to_string_lossy())) {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
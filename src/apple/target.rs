@@ -15,6 +15,7 @@ use once_cell_regex::exports::once_cell::sync::OnceCell;
 use std::{
     collections::{BTreeMap, HashMap},
     ffi::OsStr,
+    path::PathBuf,
 };
 
 fn verbosity(noise_level: opts::NoiseLevel) -> Option<&'static str> {
@@ -238,6 +239,15 @@ impl<'a> Target<'a> {
         Ok(())
     }
 
+    pub fn lib_path(&self, config: &Config, profile: Profile) -> PathBuf {
+        config.app().prefix_path(format!(
+            "target/{}/{}/lib{}.a",
+            self.triple,
+            profile.as_str(),
+            config.app().name_snake()
+        ))
+    }
+
     // NOTE: it's up to Xcode to pass the verbose flag here, so even when
     // using our build/run commands it won't get passed.
     // TODO: do something about that?
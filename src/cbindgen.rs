@@ -0,0 +1,146 @@
+use crate::{
+    env::ExplicitEnv,
+    util::{
+        self,
+        cli::{Report, Reportable},
+    },
+};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+pub static CONFIG_FILE_NAME: &str = "cbindgen.toml";
+
+// Matches the hand-written header the Xcode template used to ship, which
+// `main.mm` expects. cbindgen doesn't expand macros, so it never sees the
+// `start_app` that `#[mobile_entry_point]` generates, and we have to declare
+// that one ourselves.
+static DEFAULT_CONFIG: &str = r#"language = "C++"
+namespace = "ffi"
+pragma_once = true
+after_includes = """
+namespace ffi {
+    extern "C" {
+        void start_app();
+    }
+}
+"""
+"#;
+
+#[derive(Debug)]
+pub enum Error {
+    DefaultConfigWriteFailed { path: PathBuf, cause: io::Error },
+    DirCreationFailed { path: PathBuf, cause: io::Error },
+    CbindgenFailed(bossy::Error),
+}
+
+impl Reportable for Error {
+    fn report(&self) -> Report {
+        match self {
+            Self::DefaultConfigWriteFailed { path, cause } => Report::error(
+                format!("Failed to write default cbindgen config to {:?}", path),
+                cause,
+            ),
+            Self::DirCreationFailed { path, cause } => Report::error(
+                format!("Failed to create bindings directory at {:?}", path),
+                cause,
+            ),
+            Self::CbindgenFailed(err) => Report::action_request(
+                "Failed to generate C header via `cbindgen`",
+                format!(
+                    "Make sure cbindgen is installed (`cargo install cbindgen`) and that your `extern \"C\"` API compiles: {}",
+                    err
+                ),
+            ),
+        }
+    }
+}
+
+// Nothing here is Apple-specific, so it works anywhere cbindgen does; only
+// the Xcode project decides where the header ends up.
+pub fn generate(
+    crate_dir: &Path,
+    env: &impl ExplicitEnv,
+    header: impl Into<PathBuf>,
+) -> Result<(), Error> {
+    let header = header.into();
+    let config = crate_dir.join(CONFIG_FILE_NAME);
+    let config = if config.is_file() {
+        config
+    } else {
+        log::info!(
+            "no {:?} found; using default cbindgen config",
+            CONFIG_FILE_NAME
+        );
+        let path = util::temp_dir().join(CONFIG_FILE_NAME);
+        fs::create_dir_all(util::temp_dir())
+            .and_then(|()| fs::write(&path, DEFAULT_CONFIG))
+            .map_err(|cause| Error::DefaultConfigWriteFailed {
+                path: path.clone(),
+                cause,
+            })?;
+        path
+    };
+    if let Some(parent) = header.parent() {
+        fs::create_dir_all(parent).map_err(|cause| Error::DirCreationFailed {
+            path: parent.to_owned(),
+            cause,
+        })?;
+    }
+    bossy::Command::pure("cbindgen")
+        .with_env_vars(env.explicit_env())
+        .with_arg("--config")
+        .with_arg(&config)
+        .with_arg("--output")
+        .with_arg(&header)
+        .with_arg(crate_dir)
+        .run_and_wait()
+        .map_err(Error::CbindgenFailed)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::env::Env;
+
+    static FIXTURE_MANIFEST: &str = r#"[package]
+name = "fixture"
+version = "0.1.0"
+edition = "2018"
+
+[lib]
+crate-type = ["staticlib"]
+"#;
+
+    static FIXTURE_LIB: &str = r#"#[no_mangle]
+pub extern "C" fn fixture_add(a: i32, b: i32) -> i32 {
+    a + b
+}
+"#;
+
+    #[test]
+    fn default_config_declares_start_app() {
+        if bossy::Command::impure("cbindgen")
+            .with_arg("--version")
+            .run_and_wait_for_output()
+            .is_err()
+        {
+            eprintln!("skipping, since `cbindgen` isn't installed");
+            return;
+        }
+        let crate_dir =
+            std::env::temp_dir().join(format!("cargo-mobile-cbindgen-{}", std::process::id()));
+        fs::create_dir_all(crate_dir.join("src")).unwrap();
+        fs::write(crate_dir.join("Cargo.toml"), FIXTURE_MANIFEST).unwrap();
+        fs::write(crate_dir.join("src/lib.rs"), FIXTURE_LIB).unwrap();
+        let header = crate_dir.join("bindings/bindings.h");
+        generate(&crate_dir, &Env::new().unwrap(), &header).unwrap();
+        let header = fs::read_to_string(&header).unwrap();
+        fs::remove_dir_all(&crate_dir).unwrap();
+        assert!(header.contains("namespace ffi"), "{}", header);
+        assert!(header.contains("void start_app();"), "{}", header);
+        assert!(header.contains("fixture_add"), "{}", header);
+    }
+}
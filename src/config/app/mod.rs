@@ -14,6 +14,7 @@ use std::path::{Path, PathBuf};
 pub static KEY: &str = "app";
 
 pub static DEFAULT_ASSET_DIR: &str = "assets";
+const DEFAULT_UNIFFI: bool = false;
 #[cfg(feature = "brainium")]
 pub static DEFAULT_TEMPLATE_PACK: &str = "brainstorm";
 #[cfg(not(feature = "brainium"))]
@@ -81,6 +82,7 @@ pub struct App {
     asset_dir: PathBuf,
     #[serde(skip)]
     template_pack: Pack,
+    uniffi: bool,
 }
 
 impl App {
@@ -148,6 +150,11 @@ impl App {
 
         let template_pack = Pack::lookup_app(template_pack).map_err(Error::TemplatePackNotFound)?;
 
+        let uniffi = raw.uniffi.unwrap_or_else(|| {
+            log::info!("`{}.uniffi` not set; defaulting to {}", KEY, DEFAULT_UNIFFI);
+            DEFAULT_UNIFFI
+        });
+
         Ok(Self {
             root_dir,
             name,
@@ -155,6 +162,7 @@ impl App {
             domain,
             asset_dir,
             template_pack,
+            uniffi,
         })
     }
 
@@ -200,7 +208,20 @@ impl App {
         self.root_dir().join(&self.asset_dir)
     }
 
+    // Everything that should trigger a rebuild in watch mode.
+    pub fn watch_paths(&self) -> Vec<PathBuf> {
+        vec![
+            self.root_dir().join("src"),
+            self.manifest_path(),
+            self.asset_dir(),
+        ]
+    }
+
     pub fn template_pack(&self) -> &Pack {
         &self.template_pack
     }
+
+    pub fn uniffi(&self) -> bool {
+        self.uniffi
+    }
 }
@@ -148,6 +148,7 @@ pub struct Raw {
     pub template_pack: Option<String>,
     #[cfg(not(feature = "brainium"))]
     pub template_pack: String,
+    pub uniffi: Option<bool>,
 }
 
 impl Raw {
@@ -162,6 +163,7 @@ impl Raw {
             template_pack: None,
             #[cfg(not(feature = "brainium"))]
             template_pack: super::DEFAULT_TEMPLATE_PACK.to_owned(),
+            uniffi: None,
         })
     }
 
@@ -179,6 +181,7 @@ impl Raw {
             domain,
             asset_dir: None,
             template_pack,
+            uniffi: None,
         })
     }
 }
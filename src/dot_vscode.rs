@@ -0,0 +1,187 @@
+use crate::{
+    config::app::App,
+    util::cli::{Report, Reportable},
+};
+use serde_json::{Map, Value};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug)]
+pub enum LoadError {
+    ReadFailed { path: PathBuf, cause: io::Error },
+}
+
+impl Reportable for LoadError {
+    fn report(&self) -> Report {
+        match self {
+            Self::ReadFailed { path, cause } => Report::error(
+                format!("Failed to read VS Code config from {:?}", path),
+                cause,
+            ),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum WriteError {
+    SerializeFailed(serde_json::Error),
+    DirCreationFailed { path: PathBuf, cause: io::Error },
+    WriteFailed { path: PathBuf, cause: io::Error },
+}
+
+impl Reportable for WriteError {
+    fn report(&self) -> Report {
+        match self {
+            Self::SerializeFailed(err) => Report::error("Failed to serialize VS Code config", err),
+            Self::DirCreationFailed { path, cause } => Report::error(
+                format!("Failed to create \".vscode\" directory at {:?}", path),
+                cause,
+            ),
+            Self::WriteFailed { path, cause } => Report::error(
+                format!("Failed to write VS Code config to {:?}", path),
+                cause,
+            ),
+        }
+    }
+}
+
+// We only ever add to these files, since people tend to customize them. If one
+// can't be parsed (most likely because it has comments in it, which VS Code
+// allows but `serde_json` doesn't), it's left alone entirely.
+#[derive(Debug)]
+struct JsonFile {
+    name: &'static str,
+    contents: Option<Map<String, Value>>,
+    changed: bool,
+}
+
+impl JsonFile {
+    fn load(app: &App, name: &'static str, version: Option<&str>) -> Result<Self, LoadError> {
+        let path = app.prefix_path(".vscode").join(name);
+        let contents = if path.is_file() {
+            let bytes = fs::read(&path).map_err(|cause| LoadError::ReadFailed {
+                path: path.clone(),
+                cause,
+            })?;
+            match serde_json::from_slice(&bytes) {
+                Ok(contents) => Some(contents),
+                Err(err) => {
+                    println!(
+                        "Leaving {:?} alone, since it couldn't be parsed: {}",
+                        path, err
+                    );
+                    None
+                }
+            }
+        } else {
+            let mut contents = Map::new();
+            if let Some(version) = version {
+                contents.insert("version".to_owned(), version.into());
+            }
+            Some(contents)
+        };
+        Ok(Self {
+            name,
+            contents,
+            changed: false,
+        })
+    }
+
+    // Objects are merged, so the entries we know about stay current (e.g.
+    // after an NDK upgrade) without losing anything that was added by hand.
+    // Anything else that's already set is assumed to be customized.
+    fn insert_key(&mut self, key: &str, value: Value) {
+        if let Some(contents) = &mut self.contents {
+            if let Some(existing) = contents.get_mut(key) {
+                if let (Value::Object(existing), Value::Object(value)) = (existing, value) {
+                    for (key, value) in value {
+                        if existing.get(&key) != Some(&value) {
+                            existing.insert(key, value);
+                            self.changed = true;
+                        }
+                    }
+                }
+            } else {
+                contents.insert(key.to_owned(), value);
+                self.changed = true;
+            }
+        }
+    }
+
+    // Entries are identified by `id_key`, so anything with the same
+    // label/name as one of ours is assumed to be a customized version of it.
+    fn insert_entry(&mut self, list_key: &str, id_key: &str, entry: Value) {
+        if let Some(contents) = &mut self.contents {
+            let list = contents
+                .entry(list_key)
+                .or_insert_with(|| Value::Array(Vec::new()));
+            if let Value::Array(list) = list {
+                let present = list
+                    .iter()
+                    .any(|existing| existing.get(id_key) == entry.get(id_key));
+                if !present {
+                    list.push(entry);
+                    self.changed = true;
+                }
+            } else {
+                log::warn!("`{}` in {:?} isn't an array", list_key, self.name);
+            }
+        }
+    }
+
+    fn write(self, dir: &Path) -> Result<(), WriteError> {
+        if !self.changed {
+            return Ok(());
+        }
+        if let Some(contents) = self.contents {
+            let path = dir.join(self.name);
+            let ser =
+                serde_json::to_string_pretty(&contents).map_err(WriteError::SerializeFailed)?;
+            fs::write(&path, ser + "\n")
+                .map_err(|cause| WriteError::WriteFailed { path, cause })?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct DotVscode {
+    settings: JsonFile,
+    tasks: JsonFile,
+    launch: JsonFile,
+}
+
+impl DotVscode {
+    pub fn load(app: &App) -> Result<Self, LoadError> {
+        Ok(Self {
+            settings: JsonFile::load(app, "settings.json", None)?,
+            tasks: JsonFile::load(app, "tasks.json", Some("2.0.0"))?,
+            launch: JsonFile::load(app, "launch.json", Some("0.2.0"))?,
+        })
+    }
+
+    pub fn insert_setting(&mut self, key: &str, value: Value) {
+        self.settings.insert_key(key, value);
+    }
+
+    pub fn insert_task(&mut self, task: Value) {
+        self.tasks.insert_entry("tasks", "label", task);
+    }
+
+    pub fn insert_launch_config(&mut self, config: Value) {
+        self.launch.insert_entry("configurations", "name", config);
+    }
+
+    pub fn write(self, app: &App) -> Result<(), WriteError> {
+        let dir = app.prefix_path(".vscode");
+        fs::create_dir_all(&dir).map_err(|cause| WriteError::DirCreationFailed {
+            path: dir.clone(),
+            cause,
+        })?;
+        self.settings.write(&dir)?;
+        self.tasks.write(&dir)?;
+        self.launch.write(&dir)
+    }
+}
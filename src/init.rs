@@ -7,10 +7,11 @@ use crate::{
         metadata::{self, Metadata},
         Config,
     },
-    dot_cargo, opts, project, templating,
+    dot_cargo, dot_vscode, opts, project, templating,
     util::{
         self,
         cli::{Report, Reportable, TextWrapper},
+        CargoPackage,
     },
 };
 use std::{
@@ -51,6 +52,7 @@ pub enum Error {
     CodeCommandPresentFailed(bossy::Error),
     LldbExtensionInstallFailed(bossy::Error),
     DotCargoLoadFailed(dot_cargo::LoadError),
+    DotVscodeLoadFailed(dot_vscode::LoadError),
     HostTargetTripleDetectionFailed(util::HostTargetTripleError),
     MetadataFailed(metadata::Error),
     #[cfg(target_os = "macos")]
@@ -58,6 +60,7 @@ pub enum Error {
     AndroidEnvFailed(android::env::Error),
     AndroidInitFailed(android::project::Error),
     DotCargoWriteFailed(dot_cargo::WriteError),
+    DotVscodeWriteFailed(dot_vscode::WriteError),
     DotFirstInitDeleteFailed {
         path: PathBuf,
         cause: io::Error,
@@ -76,6 +79,7 @@ impl Reportable for Error {
             Self::CodeCommandPresentFailed(err) => Report::error("Failed to check for presence of `code` command", err),
             Self::LldbExtensionInstallFailed(err) => Report::error("Failed to install CodeLLDB extension", err),
             Self::DotCargoLoadFailed(err) => err.report(),
+            Self::DotVscodeLoadFailed(err) => err.report(),
             Self::HostTargetTripleDetectionFailed(err) => err.report(),
             Self::MetadataFailed(err) => err.report(),
             Self::AndroidEnvFailed(err) => err.report(),
@@ -83,6 +87,7 @@ impl Reportable for Error {
             #[cfg(target_os = "macos")]
             Self::AppleInitFailed(err) => err.report(),
             Self::DotCargoWriteFailed(err) => err.report(),
+            Self::DotVscodeWriteFailed(err) => err.report(),
             Self::DotFirstInitDeleteFailed { path, cause } => Report::action_request(format!("Failed to delete first init dot file {:?}; the project generated successfully, but `cargo mobile init` will have unexpected results unless you manually delete this file!", path), cause),
             Self::OpenInEditorFailed(err) => Report::error("Failed to open project in editor (your project generated successfully though, so no worries!)", err),
         }
@@ -159,6 +164,36 @@ pub fn exec(
         util::host_target_triple().map_err(Error::HostTargetTripleDetectionFailed)?,
     );
 
+    let mut dot_vscode =
+        dot_vscode::DotVscode::load(config.app()).map_err(Error::DotVscodeLoadFailed)?;
+    // `build.target` is already set to the host, so this is about as simple
+    // as it gets, as long as the template pack provided a `-desktop` bin.
+    let desktop_bin = format!("{}-desktop", config.app().name());
+    match CargoPackage::load(&config.app().manifest_path()) {
+        Ok(package) if package.has_bin(&desktop_bin) => {
+            dot_vscode.insert_launch_config(serde_json::json!({
+                "name": "Debug on desktop",
+                "type": "lldb",
+                "request": "launch",
+                "cargo": {
+                    "args": [
+                        "build",
+                        format!("--bin={}", desktop_bin),
+                    ],
+                },
+            }));
+        }
+        Ok(_) => log::info!(
+            "no `{}` bin found, so there's no desktop launch config",
+            desktop_bin
+        ),
+        Err(err) => log::warn!(
+            "failed to check for a `{}` bin, so there's no desktop launch config: {}",
+            desktop_bin,
+            err
+        ),
+    }
+
     let metadata = Metadata::load(&config.app().root_dir()).map_err(Error::MetadataFailed)?;
 
     // Generate Xcode project
@@ -183,7 +218,14 @@ pub fn exec(
     if metadata.android().supported() {
         match android::env::Env::new() {
             Ok(env) => {
-                android::project::gen(config.android(), &env, &bike, &filter, &mut dot_cargo)
+                android::project::gen(
+                    config.android(),
+                    &env,
+                    &bike,
+                    &filter,
+                    &mut dot_cargo,
+                    &mut dot_vscode,
+                )
                     .map_err(Error::AndroidInitFailed)?
             }
             Err(err) => {
@@ -207,6 +249,9 @@ pub fn exec(
     dot_cargo
         .write(config.app())
         .map_err(Error::DotCargoWriteFailed)?;
+    dot_vscode
+        .write(config.app())
+        .map_err(Error::DotVscodeWriteFailed)?;
     if dot_first_init_exists {
         log::info!("deleting first init dot file at {:?}", dot_first_init_path);
         fs::remove_file(&dot_first_init_path).map_err(|cause| Error::DotFirstInitDeleteFailed {
@@ -3,9 +3,11 @@
 pub mod android;
 #[cfg(target_os = "macos")]
 pub mod apple;
+pub mod cbindgen;
 pub mod config;
 pub mod device;
 mod dot_cargo;
+mod dot_vscode;
 pub mod env;
 pub mod init;
 pub mod opts;
@@ -13,6 +15,7 @@ pub mod os;
 mod project;
 pub mod target;
 mod templating;
+mod uniffi;
 pub mod update;
 pub mod util;
 
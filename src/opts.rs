@@ -46,6 +46,8 @@ yes_or_no!(ReinstallDeps);
 
 yes_or_no!(OpenInEditor);
 
+yes_or_no!(StripSymbols);
+
 #[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub enum Profile {
     Debug,
@@ -76,3 +78,29 @@ impl Profile {
         }
     }
 }
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+impl Default for OutputFormat {
+    fn default() -> Self {
+        Self::Text
+    }
+}
+
+impl OutputFormat {
+    pub fn from_json_flag(flag: bool) -> Self {
+        if flag {
+            Self::Json
+        } else {
+            Self::Text
+        }
+    }
+
+    pub fn json(self) -> bool {
+        matches!(self, Self::Json)
+    }
+}
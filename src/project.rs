@@ -2,15 +2,17 @@ use crate::{
     config::Config,
     templating::{self, FancyPackResolveError},
     util::{
+        self,
         cli::{Report, Reportable},
         Git,
     },
 };
-use std::path::PathBuf;
+use std::{iter, path::PathBuf};
 
 #[derive(Debug)]
 pub enum Error {
     GitInitFailed(bossy::Error),
+    NoHomeDir(util::NoHomeDir),
     TemplatePackResolveFailed(FancyPackResolveError),
     ProcessingFailed {
         src: PathBuf,
@@ -23,6 +25,7 @@ impl Reportable for Error {
     fn report(&self) -> Report {
         match self {
             Self::GitInitFailed(err) => Report::error("Failed to initialize git", err),
+            Self::NoHomeDir(err) => Report::error("Failed to find shared templates", err),
             Self::TemplatePackResolveFailed(err) => {
                 Report::error("Failed to resolve template pack", err)
             }
@@ -53,7 +56,8 @@ pub fn gen(
         .resolve(git, submodule_commit.as_deref())
         .map_err(Error::TemplatePackResolveFailed)?;
     log::info!("template pack chain: {:#?}", pack_chain);
-    for pack in pack_chain {
+    let shared_pack = templating::shared_pack_dir().map_err(Error::NoHomeDir)?;
+    for pack in iter::once(shared_pack.as_path()).chain(pack_chain) {
         log::info!("traversing template pack {:#?}", pack);
         bike.filter_and_process(&pack, &root, |_| (), filter.fun())
             .map_err(|cause| Error::ProcessingFailed {
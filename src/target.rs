@@ -98,9 +98,15 @@ where
     })
 }
 
-pub fn call_for_targets_with_fallback<'a, Iter, I, T, U, E, F>(
+// Used by commands that take targets, for when none are given. Probing for a
+// device can mean prompting, which is a lot to ask just to run `cargo check`,
+// so that only happens when `device_target` is set. Otherwise, or if no
+// device is found, the configured default targets are used.
+pub fn call_for_targets_with_defaults<'a, Iter, I, T, U, E, F>(
     targets: Iter,
-    fallback: &'a dyn Fn(U) -> Option<&'a T>,
+    defaults: &'a [String],
+    device_target: bool,
+    detect: &'a dyn Fn(U) -> Option<&'a T>,
     arg: U,
     f: F,
 ) -> Result<Result<(), E>, TargetInvalid>
@@ -110,12 +116,37 @@ where
     T: TargetTrait<'a>,
     F: Fn(&T) -> Result<(), E>,
 {
-    get_targets(targets, Some((fallback, arg))).map(|targets| {
-        for target in targets {
-            f(target)?;
+    let detected = if targets.len() == 0 && device_target {
+        let detected = detect(arg);
+        if let Some(target) = detected {
+            println!(
+                "No targets specified; using {} to match connected device",
+                target.triple()
+            );
+        } else {
+            println!(
+                "No targets specified and no connected device was selected; falling back on default targets {:?}",
+                defaults
+            );
         }
-        Ok(())
-    })
+        detected
+    } else {
+        None
+    };
+    let targets = if let Some(target) = detected {
+        vec![target]
+    } else if targets.len() == 0 {
+        if !device_target {
+            println!(
+                "No targets specified; using default targets {:?} (pass `--device-target` to use the target of a connected device instead)",
+                defaults
+            );
+        }
+        get_targets::<_, _, _, ()>(defaults.iter(), None)?
+    } else {
+        get_targets::<_, _, _, ()>(targets, None)?
+    };
+    Ok(targets.into_iter().try_for_each(f))
 }
 
 pub fn call_for_targets<'a, Iter, I, T, E, F>(
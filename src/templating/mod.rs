@@ -23,6 +23,12 @@ fn app_pack_dir() -> Result<PathBuf, util::NoHomeDir> {
     util::install_dir().map(|dir| dir.join("templates/apps"))
 }
 
+// Glue the app templates have in common, which gets generated into every
+// project ahead of its app template pack.
+pub fn shared_pack_dir() -> Result<PathBuf, util::NoHomeDir> {
+    util::install_dir().map(|dir| dir.join("templates/shared"))
+}
+
 #[derive(Debug)]
 pub enum LookupError {
     NoHomeDir(util::NoHomeDir),
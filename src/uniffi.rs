@@ -0,0 +1,118 @@
+use crate::{
+    config::app::App,
+    env::ExplicitEnv,
+    util::cli::{Report, Reportable},
+};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+#[derive(Clone, Copy, Debug)]
+pub enum Language {
+    Kotlin,
+    Swift,
+}
+
+impl Language {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Kotlin => "kotlin",
+            Self::Swift => "swift",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    DirCleaningFailed { path: PathBuf, cause: io::Error },
+    DirCreationFailed { path: PathBuf, cause: io::Error },
+    BindgenFailed(bossy::Error),
+    ModulemapMergeFailed { path: PathBuf, cause: io::Error },
+}
+
+impl Reportable for Error {
+    fn report(&self) -> Report {
+        match self {
+            Self::DirCleaningFailed { path, cause } => Report::error(
+                format!("Failed to clear old UniFFI bindings from {:?}", path),
+                cause,
+            ),
+            Self::DirCreationFailed { path, cause } => Report::error(
+                format!("Failed to create UniFFI bindings directory at {:?}", path),
+                cause,
+            ),
+            Self::BindgenFailed(err) => Report::action_request(
+                "Failed to generate UniFFI bindings",
+                format!("UniFFI's bindgen is run via `cargo run --bin uniffi-bindgen`, so your project needs to define that binary (see https://mozilla.github.io/uniffi-rs/tutorial/foreign_language_bindings.html): {}", err),
+            ),
+            Self::ModulemapMergeFailed { path, cause } => Report::error(
+                format!("Failed to merge UniFFI module maps in {:?}", path),
+                cause,
+            ),
+        }
+    }
+}
+
+// The bindgen version has to match the `uniffi` version the lib was built
+// with, so rather than relying on whatever's installed, we use the
+// `uniffi-bindgen` binary from the app's own workspace. Old bindings are
+// deleted first, so anything removed from the Rust side goes away too.
+pub fn generate(
+    app: &App,
+    env: &impl ExplicitEnv,
+    lib: impl Into<PathBuf>,
+    language: Language,
+    out_dir: impl Into<PathBuf>,
+) -> Result<(), Error> {
+    let out_dir = out_dir.into();
+    if out_dir.is_dir() {
+        fs::remove_dir_all(&out_dir).map_err(|cause| Error::DirCleaningFailed {
+            path: out_dir.clone(),
+            cause,
+        })?;
+    }
+    fs::create_dir_all(&out_dir).map_err(|cause| Error::DirCreationFailed {
+        path: out_dir.clone(),
+        cause,
+    })?;
+    bossy::Command::pure("cargo")
+        .with_env_vars(env.explicit_env())
+        .with_args(&[
+            "run",
+            "--quiet",
+            "--bin",
+            "uniffi-bindgen",
+            "--manifest-path",
+        ])
+        .with_arg(app.manifest_path())
+        .with_args(&["--", "generate", "--library"])
+        .with_arg(lib.into())
+        .with_args(&["--language", language.as_str(), "--out-dir"])
+        .with_arg(&out_dir)
+        .run_and_wait()
+        .map_err(Error::BindgenFailed)?;
+    if let Language::Swift = language {
+        merge_modulemaps(&out_dir).map_err(|cause| Error::ModulemapMergeFailed {
+            path: out_dir,
+            cause,
+        })?;
+    }
+    Ok(())
+}
+
+// Library mode writes a module map for each crate, but Clang only looks for
+// ones named `module.modulemap`, so they all get combined into one of those.
+// That's what the Xcode project's `SWIFT_INCLUDE_PATHS` points at.
+fn merge_modulemaps(out_dir: &Path) -> io::Result<()> {
+    let mut merged = String::new();
+    for entry in fs::read_dir(out_dir)? {
+        let path = entry?.path();
+        if path.extension() == Some("modulemap".as_ref()) {
+            merged.push_str(&fs::read_to_string(&path)?);
+            merged.push('\n');
+            fs::remove_file(&path)?;
+        }
+    }
+    fs::write(out_dir.join("module.modulemap"), merged)
+}
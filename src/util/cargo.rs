@@ -1,5 +1,10 @@
 use crate::env::ExplicitEnv;
-use std::path::PathBuf;
+use serde::Deserialize;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
 
 #[derive(Debug)]
 pub struct CargoCommand<'a> {
@@ -107,3 +112,59 @@ impl<'a> CargoCommand<'a> {
         self.into_command_inner(bossy::Command::pure("cargo").with_env_vars(env.explicit_env()))
     }
 }
+
+#[derive(Debug, Error)]
+pub enum CargoPackageError {
+    #[error("Failed to run `cargo metadata`: {0}")]
+    CommandFailed(#[from] bossy::Error),
+    #[error("Failed to parse `cargo metadata` output: {0}")]
+    ParseFailed(#[from] serde_json::Error),
+    #[error("`cargo metadata` didn't list a package with manifest {0:?}")]
+    PackageMissing(PathBuf),
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CargoTarget {
+    pub name: String,
+    pub kind: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CargoPackage {
+    pub version: String,
+    pub targets: Vec<CargoTarget>,
+    manifest_path: PathBuf,
+}
+
+impl CargoPackage {
+    // This goes through `cargo metadata` rather than reading the manifest
+    // directly, so anything inherited from a workspace or discovered
+    // automatically (i.e. bins in `src/bin`) is accounted for.
+    pub fn load(manifest_path: &Path) -> Result<Self, CargoPackageError> {
+        #[derive(Deserialize)]
+        struct Metadata {
+            packages: Vec<CargoPackage>,
+        }
+
+        let manifest_path = fs::canonicalize(manifest_path).unwrap_or_else(|err| {
+            log::info!("failed to canonicalize {:?}: {}", manifest_path, err);
+            manifest_path.to_owned()
+        });
+        let output = bossy::Command::impure("cargo")
+            .with_args(&["metadata", "--no-deps", "--format-version", "1"])
+            .with_arg("--manifest-path")
+            .with_arg(&manifest_path)
+            .run_and_wait_for_output()?;
+        serde_json::from_slice::<Metadata>(output.stdout())?
+            .packages
+            .into_iter()
+            .find(|package| package.manifest_path == manifest_path)
+            .ok_or(CargoPackageError::PackageMissing(manifest_path))
+    }
+
+    pub fn has_bin(&self, name: &str) -> bool {
+        self.targets
+            .iter()
+            .any(|target| target.name == name && target.kind.iter().any(|kind| kind == "bin"))
+    }
+}
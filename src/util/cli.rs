@@ -108,6 +108,24 @@ pub struct Profile {
     pub profile: opts::Profile,
 }
 
+#[derive(Clone, Copy, Debug, Default, StructOpt)]
+pub struct OutputFormat {
+    #[structopt(
+        long = "json",
+        help = "Print output as JSON",
+        parse(from_flag = opts::OutputFormat::from_json_flag),
+    )]
+    pub output_format: opts::OutputFormat,
+}
+
+pub fn parse_key_value(s: &str) -> Result<(String, String), String> {
+    let mut parts = s.splitn(2, '=');
+    match (parts.next(), parts.next()) {
+        (Some(key), Some(value)) if !key.is_empty() => Ok((key.to_owned(), value.to_owned())),
+        _ => Err(format!("{:?} isn't in the form `KEY=VALUE`", s)),
+    }
+}
+
 pub type TextWrapper = textwrap::Wrapper<'static, textwrap::NoHyphenation>;
 
 #[derive(Clone, Copy, Debug)]
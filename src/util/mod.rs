@@ -4,6 +4,7 @@ mod git;
 pub mod ln;
 mod path;
 pub mod prompt;
+pub mod watch;
 
 pub use self::{cargo::*, git::*, path::*};
 
@@ -11,6 +12,7 @@ use self::cli::{Report, Reportable};
 use crate::os;
 use once_cell_regex::regex;
 use std::{
+    ffi::OsStr,
     fmt::{self, Display},
     io::{self, Write},
     path::Path,
@@ -172,6 +174,15 @@ impl RustVersion {
     }
 }
 
+// Single quotes don't allow any escapes, so we have to close the quote, add an
+// escaped quote, and then re-open it.
+pub fn shell_quote(value: impl AsRef<OsStr>) -> String {
+    format!(
+        "'{}'",
+        value.as_ref().to_string_lossy().replace('\'', r"'\''")
+    )
+}
+
 pub fn prepend_to_path(path: impl Display, base_path: impl Display) -> String {
     format!("{}:{}", path, base_path)
 }
@@ -0,0 +1,85 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, SystemTime},
+};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(300);
+
+type Snapshot = BTreeMap<PathBuf, SystemTime>;
+
+// Polling is crude, but it's the same everywhere, and a crate's sources and
+// assets are small enough that walking them a few times a second is cheap.
+// Anything gitignored (i.e. `target`) is skipped, so builds don't trigger
+// more builds.
+#[derive(Debug)]
+pub struct Watcher {
+    paths: Vec<PathBuf>,
+    snapshot: Snapshot,
+}
+
+impl Watcher {
+    pub fn new(paths: impl IntoIterator<Item = PathBuf>) -> Self {
+        let paths = paths.into_iter().collect::<Vec<_>>();
+        let snapshot = scan(&paths);
+        Self { paths, snapshot }
+    }
+
+    pub fn paths(&self) -> &[PathBuf] {
+        &self.paths
+    }
+
+    // Blocks until something changes, then keeps waiting until things settle
+    // down, so saving a bunch of files at once only results in one rebuild.
+    pub fn wait(&mut self) -> Vec<PathBuf> {
+        let mut changed = Vec::new();
+        loop {
+            thread::sleep(POLL_INTERVAL);
+            let snapshot = scan(&self.paths);
+            let new_changes = diff(&self.snapshot, &snapshot);
+            self.snapshot = snapshot;
+            if new_changes.is_empty() && !changed.is_empty() {
+                break;
+            }
+            changed.extend(new_changes);
+        }
+        changed.sort();
+        changed.dedup();
+        changed
+    }
+}
+
+fn scan(paths: &[PathBuf]) -> Snapshot {
+    let mut snapshot = Snapshot::new();
+    for path in paths {
+        for entry in ignore::WalkBuilder::new(path)
+            .hidden(false)
+            .build()
+            .filter_map(Result::ok)
+        {
+            let modified = entry
+                .metadata()
+                .ok()
+                .filter(|metadata| metadata.is_file())
+                .and_then(|metadata| metadata.modified().ok());
+            if let Some(modified) = modified {
+                snapshot.insert(entry.into_path(), modified);
+            }
+        }
+    }
+    snapshot
+}
+
+fn diff(old: &Snapshot, new: &Snapshot) -> Vec<PathBuf> {
+    let modified_or_added = new
+        .iter()
+        .filter(|(path, modified)| old.get(*path) != Some(modified))
+        .map(|(path, _)| path);
+    let removed = old.keys().filter(|path| !new.contains_key(*path));
+    modified_or_added.chain(removed).cloned().collect()
+}
+
+pub fn all_under(changed: &[PathBuf], dir: &Path) -> bool {
+    !changed.is_empty() && changed.iter().all(|path| path.starts_with(dir))
+}
@@ -0,0 +1,39 @@
+//! Glue shared by all of the app templates. `cargo mobile init` regenerates
+//! this, so any changes made here will be overwritten.
+
+/// Puts the env vars given to `cargo android run` into the environment. They
+/// live in a file in the app's own files dir, so each app only sees its own.
+#[cfg(target_os = "android")]
+pub fn load_launch_env() {
+    // The process name is the package name, plus a `:suffix` for any
+    // secondary processes.
+    let package = std::fs::read("/proc/self/cmdline")
+        .ok()
+        .and_then(|cmdline| {
+            let name = cmdline.split(|&byte| byte == 0).next()?;
+            let name = String::from_utf8_lossy(name);
+            name.split(':').next().map(ToOwned::to_owned)
+        });
+    let path =
+        package.map(|package| format!("/sdcard/Android/data/{}/files/cargo-mobile-env", package));
+    if let Some(contents) = path.and_then(|path| std::fs::read_to_string(path).ok()) {
+        for line in contents.lines() {
+            let mut parts = line.splitn(2, '=');
+            if let (Some(key), Some(value)) = (parts.next(), parts.next()) {
+                std::env::set_var(key, value);
+            }
+        }
+    }
+}
+
+/// Where to load an asset from, given its path within the asset dir.
+/// `cargo android sync-assets` and `cargo android run --watch` push changed
+/// assets straight to the device and set `SYNCED_ASSET_DIR`, so they can be
+/// updated without reinstalling; anything that hasn't been synced comes from
+/// the usual place.
+pub fn asset_path(path: &str) -> std::path::PathBuf {
+    std::env::var_os("SYNCED_ASSET_DIR")
+        .map(|dir| std::path::Path::new(&dir).join(path))
+        .filter(|synced| synced.is_file())
+        .unwrap_or_else(|| std::path::Path::new("assets").join(path))
+}